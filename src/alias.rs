@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::event::ChatEvent;
+use crate::message::Message;
+
+// `+44 7700 900123`, `0044 (7700) 900-123` -> `+447700900123`, anything that isn't an
+// international number is left alone
+pub fn e164(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let rest = raw.strip_prefix('+').or_else(|| raw.strip_prefix("00"))?;
+
+    let mut digits = String::from("+");
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            ' ' | '-' | '.' | '(' | ')' | '\u{a0}' | '\u{202f}' => {}
+            _ => return None,
+        }
+    }
+
+    match digits.len() - 1 {
+        8..=15 => Some(digits),
+        _ => None,
+    }
+}
+
+// raw author names mapped to the identity they belong to, phone numbers are matched in their
+// e.164 form
+#[derive(Debug, Default)]
+pub struct Aliases {
+    names: HashMap<String, String>,
+}
+
+impl Aliases {
+    // files list the raw names of every identity, `{"Foo Bar": ["Foo", "+44 7700 900123"]}`
+    fn from_map(map: HashMap<String, Vec<String>>) -> Aliases {
+        let mut aliases = Aliases::default();
+
+        for (canonical, names) in map {
+            for name in names {
+                aliases.names.insert(key(&name), canonical.clone());
+            }
+        }
+
+        aliases
+    }
+
+    pub fn from_json(raw: &str) -> serde_json::Result<Aliases> {
+        serde_json::from_str(raw).map(Aliases::from_map)
+    }
+
+    pub fn from_toml(raw: &str) -> Result<Aliases, toml::de::Error> {
+        toml::from_str(raw).map(Aliases::from_map)
+    }
+
+    // the format is told by the extension, json unless it's `.toml`
+    pub fn read(path: &Path) -> io::Result<Aliases> {
+        let raw = fs::read_to_string(path)?;
+        let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+        {
+            Aliases::from_toml(&raw).map_err(|err| invalid(err.to_string()))
+        } else {
+            Aliases::from_json(&raw).map_err(|err| invalid(err.to_string()))
+        }
+    }
+
+    pub fn resolve(&self, author: &str) -> String {
+        let key = key(author);
+
+        match self.names.get(&key) {
+            Some(canonical) => canonical.clone(),
+            None => key,
+        }
+    }
+
+    // messages have to come in chronological order, a new phone number is only known to belong
+    // to someone from the moment it's announced. the file has the last word on a number
+    pub fn apply(&mut self, message: &mut Message) {
+        if let Some(ChatEvent::NumberChanged { from, to: Some(to) }) = &message.event {
+            let canonical = self.resolve(from);
+            self.names.entry(key(to)).or_insert(canonical);
+        }
+
+        message.author = self.resolve(&message.author);
+    }
+}
+
+fn key(name: &str) -> String {
+    e164(name).unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversation::Conversation;
+
+    #[test]
+    fn e164_works() {
+        assert_eq!(e164("+44 7700 900123"), Some(String::from("+447700900123")));
+        assert_eq!(
+            e164("0044 (7700) 900-123"),
+            Some(String::from("+447700900123"))
+        );
+        assert_eq!(
+            e164("+1\u{a0}555-010-0199"),
+            Some(String::from("+15550100199"))
+        );
+        assert_eq!(e164("07700 900123"), None);
+        assert_eq!(e164("+44 Foo"), None);
+        assert_eq!(e164("+123"), None);
+    }
+
+    #[test]
+    fn from_json_works() {
+        let aliases = Aliases::from_json(r#"{"Foo Bar": ["Foo", "+44 7700 900123"]}"#).unwrap();
+
+        assert_eq!(aliases.resolve("Foo"), "Foo Bar");
+        assert_eq!(aliases.resolve("+447700900123"), "Foo Bar");
+        assert_eq!(aliases.resolve("Foo Bar"), "Foo Bar");
+        assert_eq!(aliases.resolve("Baz"), "Baz");
+        assert_eq!(aliases.resolve("+44 7700 900456"), "+447700900456");
+    }
+
+    #[test]
+    fn from_toml_works() {
+        let aliases = Aliases::from_toml(
+            r#"
+"Foo Bar" = ["Foo", "+44 7700 900123"]
+Baz = ["Bazza"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(aliases.resolve("+44 7700 900123"), "Foo Bar");
+        assert_eq!(aliases.resolve("Bazza"), "Baz");
+        assert!(Aliases::from_toml("Foo = 1").is_err());
+    }
+
+    #[test]
+    fn apply_follows_number_changes() {
+        let c = Conversation::from_str(
+            "
+[2019-09-11, 01:57:17] +44 7700 900123: Hey
+[2019-09-11, 01:58:17] Group: \u{200e}+44 7700 900123 changed to +44 7700 900456
+[2019-09-11, 01:59:17] +44 7700 900456: new number
+[2019-09-11, 02:00:00] Bar: ok
+",
+        )
+        .unwrap();
+
+        let mut aliases = Aliases::from_json(r#"{"Foo": ["+44 7700 900123"]}"#).unwrap();
+        let authors: Vec<String> = c
+            .into_messages()
+            .into_iter()
+            .map(|mut m| {
+                aliases.apply(&mut m);
+                m.author
+            })
+            .collect();
+
+        assert_eq!(authors, vec!["Foo", "Group", "Foo", "Bar"]);
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use zip::result::{ZipError, ZipResult};
+use zip::ZipArchive;
+
+use crate::encoding;
+
+// contents of an export made "with media"
+#[derive(Debug)]
+pub struct Archive {
+    pub chat: String,
+    // size of every attachment in the archive, keyed by its file name
+    pub files: HashMap<String, u64>,
+}
+
+// ios names the chat `_chat.txt`, android uses `WhatsApp Chat with Foo.txt`
+fn is_chat(name: &str) -> bool {
+    name == "_chat.txt" || (name.starts_with("WhatsApp Chat") && name.ends_with(".txt"))
+}
+
+pub fn read<R: Read + Seek>(reader: R) -> ZipResult<Archive> {
+    let mut zip = ZipArchive::new(reader)?;
+
+    let mut chat = None;
+    let mut files = HashMap::new();
+
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        let name = match file.name().rsplit('/').next() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        if chat.is_none() && is_chat(&name) {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            chat = Some(encoding::decode_bytes(bytes)?);
+        } else {
+            files.insert(name, file.size());
+        }
+    }
+
+    match chat {
+        Some(chat) => Ok(Archive { chat, files }),
+        None => Err(ZipError::FileNotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Cursor, Write};
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn mock(entries: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+        for (name, contents) in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    #[test]
+    fn read_works() {
+        let a = read(mock(&[
+            ("_chat.txt", b"[2019-09-11, 01:57:17] Foo: Bar"),
+            ("00000012-PHOTO-2019-09-11-01-57-17.jpg", &[0; 42]),
+        ]))
+        .unwrap();
+
+        assert_eq!(a.chat, "[2019-09-11, 01:57:17] Foo: Bar");
+        assert_eq!(
+            a.files.get("00000012-PHOTO-2019-09-11-01-57-17.jpg"),
+            Some(&42)
+        );
+    }
+
+    #[test]
+    fn read_finds_android_chat() {
+        let a = read(mock(&[(
+            "WhatsApp Chat with Foo.txt",
+            b"9/11/19, 1:57 AM - Foo: Bar",
+        )]))
+        .unwrap();
+
+        assert_eq!(a.chat, "9/11/19, 1:57 AM - Foo: Bar");
+        assert!(a.files.is_empty());
+    }
+
+    #[test]
+    fn read_fails_without_chat() {
+        let err = read(mock(&[("IMG-20190911-WA0001.jpg", &[0; 8])])).unwrap_err();
+        assert!(matches!(err, ZipError::FileNotFound));
+    }
+}
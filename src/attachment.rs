@@ -0,0 +1,118 @@
+use crate::locale::Locale;
+use crate::message::MessageType;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    // only known when the export comes with its media
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl Attachment {
+    // ios:     <attached: 00000012-PHOTO-2019-09-11-01-57-17.jpg>
+    // android: IMG-20190911-WA0001.jpg (file attached)
+    pub fn parse(text: &str, locale: &Locale) -> Option<Attachment> {
+        let patterns = locale.patterns();
+        let first = text.lines().next().unwrap_or_default().trim();
+
+        patterns
+            .attached
+            .captures(first)
+            .or_else(|| patterns.file_attached.captures(first))
+            .map(|c| Attachment {
+                name: c["name"].to_string(),
+                size: None,
+            })
+    }
+
+    pub fn kind(&self) -> MessageType {
+        let name = self.name.to_uppercase();
+        let extension = name.rsplit('.').next().unwrap_or_default();
+
+        if name.contains("-STICKER-") || name.starts_with("STK-") {
+            MessageType::Sticker
+        } else if name.contains("-GIF-") {
+            MessageType::Gif
+        } else if name.contains("-PHOTO-") || name.starts_with("IMG-") {
+            MessageType::Image
+        } else if name.contains("-VIDEO-") || name.starts_with("VID-") {
+            MessageType::Video
+        } else if name.contains("-AUDIO-") || name.starts_with("AUD-") || name.starts_with("PTT-") {
+            MessageType::Audio
+        } else if extension == "VCF" {
+            MessageType::Contact
+        } else {
+            match extension {
+                "GIF" => MessageType::Gif,
+                "JPG" | "JPEG" | "PNG" | "HEIC" | "WEBP" => MessageType::Image,
+                "MP4" | "MOV" | "3GP" => MessageType::Video,
+                "OPUS" | "M4A" | "MP3" | "AAC" | "OGG" => MessageType::Audio,
+                _ => MessageType::Document(self.name.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let ios = Attachment::parse(
+            "\u{200e}<attached: 00000012-PHOTO-2019-09-11-01-57-17.jpg>",
+            Locale::english(),
+        );
+        assert_eq!(ios.unwrap().name, "00000012-PHOTO-2019-09-11-01-57-17.jpg");
+
+        let android = Attachment::parse(
+            "VID-20190911-WA0001.mp4 (file attached)\nlook at this",
+            Locale::english(),
+        );
+        assert_eq!(android.unwrap().name, "VID-20190911-WA0001.mp4");
+
+        assert_eq!(
+            Attachment::parse("I <attached: nothing> really", Locale::english()),
+            None
+        );
+
+        let de = Attachment::parse(
+            "<Anhang: 00000012-PHOTO-2019-09-11-01-57-17.jpg>",
+            Locale::find("de").unwrap(),
+        );
+        assert_eq!(de.unwrap().name, "00000012-PHOTO-2019-09-11-01-57-17.jpg");
+    }
+
+    #[test]
+    fn kind_works() {
+        macro_rules! assert_kind {
+            ($name: expr, $kind: expr) => {
+                let a = Attachment {
+                    name: String::from($name),
+                    size: None,
+                };
+                assert_eq!(a.kind(), $kind, "with: {}", $name);
+            };
+        }
+
+        assert_kind!("00000012-PHOTO-2019-09-11-01-57-17.jpg", MessageType::Image);
+        assert_kind!(
+            "00000013-AUDIO-2019-09-11-01-57-17.opus",
+            MessageType::Audio
+        );
+        assert_kind!("VID-20190911-WA0001.mp4", MessageType::Video);
+        assert_kind!("PTT-20190911-WA0002.opus", MessageType::Audio);
+        assert_kind!("00000014-Foo Bar.vcf", MessageType::Contact);
+        assert_kind!(
+            "00000015-STICKER-2019-09-11-01-57-17.webp",
+            MessageType::Sticker
+        );
+        assert_kind!("00000016-GIF-2019-09-11-01-57-17.mp4", MessageType::Gif);
+        assert_kind!("party.gif", MessageType::Gif);
+        assert_kind!(
+            "report.pdf",
+            MessageType::Document(String::from("report.pdf"))
+        );
+    }
+}
@@ -1,102 +1,281 @@
-use std::collections::HashMap;
-use std::string;
-
-use std::ops::Add;
-use std::ops::Sub;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::iter::FromIterator;
 
 use chrono::prelude::*;
-use chrono::Duration;
-use regex::Regex;
+use chrono::{Duration, Months};
+use chrono_tz::Tz;
+use serde::{Serialize, Serializer};
 
+use crate::dialect::Dialect;
 use crate::emoji::{self, Emojis};
-use crate::message::{Message, MessageError, MessageErrorKind, Result};
+use crate::format::Format;
+use crate::locale::Locale;
+use crate::message::{Message, MessageError, MessageErrorKind, MessageType, Result};
+use crate::stream::Messages;
 
-#[derive(Clone, Copy, Serialize, Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum TimelineType {
+    Hourly,
     Daily,
-    Weekly,
+    Weekly(Week),
     Monthly,
+    Quarterly,
     Yearly,
+    Interval(Interval),
+}
+
+// periods of a fixed length counted from `anchor`, which has to be on the hour. without one
+// they're counted from midnight of the day the conversation starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval {
+    pub length: Duration,
+    pub anchor: Option<NaiveDateTime>,
+}
+
+impl Interval {
+    // `6h`, `14d` or `2w`
+    fn from_name(name: &str) -> Option<Interval> {
+        let unit = name.chars().last()?;
+        let count: i64 = name[..name.len() - unit.len_utf8()].parse().ok()?;
+
+        let length = match unit {
+            'h' => Duration::try_hours(count)?,
+            'd' => Duration::try_days(count)?,
+            'w' => Duration::try_weeks(count)?,
+            _ => return None,
+        };
+
+        if count > 0 {
+            Some(Interval {
+                length,
+                anchor: None,
+            })
+        } else {
+            None
+        }
+    }
+
+    // an anchor far off the conversation can put the start out of the range of dates, the
+    // earliest one stands in for it
+    fn start_of(&self, date: &NaiveDateTime) -> NaiveDateTime {
+        let anchor = self
+            .anchor
+            .unwrap_or_else(|| date.date().and_time(NaiveTime::MIN));
+        let length = self.length.num_seconds();
+        let count = (*date - anchor).num_seconds().div_euclid(length);
+
+        count
+            .checked_mul(length)
+            .and_then(Duration::try_seconds)
+            .and_then(|offset| anchor.checked_add_signed(offset))
+            .unwrap_or(NaiveDateTime::MIN)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hours = self.length.num_hours();
+
+        match hours {
+            _ if hours % (24 * 7) == 0 => write!(f, "{}w", hours / (24 * 7)),
+            _ if hours % 24 == 0 => write!(f, "{}d", hours / 24),
+            _ => write!(f, "{}h", hours),
+        }
+    }
+}
+
+// weeks start on monday unless set, `iso` labels them with their iso year and week, `2019-W37`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Week {
+    pub start: Weekday,
+    pub iso: bool,
+}
+
+impl Default for Week {
+    fn default() -> Self {
+        Week {
+            start: Weekday::Mon,
+            iso: false,
+        }
+    }
+}
+
+impl Week {
+    // weeks that don't start on monday are labelled after the iso week their monday is in
+    fn label(&self, start: &NaiveDateTime) -> String {
+        let monday =
+            start.date() + Duration::days(i64::from(7 - self.start.num_days_from_monday()) % 7);
+        let week = monday.iso_week();
+
+        format!("{}-W{:02}", week.year(), week.week())
+    }
 }
 
 impl TimelineType {
-    fn as_days(&self) -> u32 {
+    // names as taken by `--timeline`, the anchor only goes with intervals
+    pub fn from_name(name: &str, anchor: Option<NaiveDateTime>) -> Option<TimelineType> {
+        let kind = match name {
+            "hourly" => TimelineType::Hourly,
+            "daily" => TimelineType::Daily,
+            "weekly" => TimelineType::Weekly(Week::default()),
+            "monthly" => TimelineType::Monthly,
+            "quarterly" => TimelineType::Quarterly,
+            "yearly" => TimelineType::Yearly,
+            _ => {
+                let interval = Interval::from_name(name)?;
+                if anchor.is_some_and(|a| a.minute() != 0 || a.second() != 0) {
+                    return None;
+                }
+
+                return Some(TimelineType::Interval(Interval { anchor, ..interval }));
+            }
+        };
+
+        match anchor {
+            Some(_) => None,
+            None => Some(kind),
+        }
+    }
+
+    // weekly timelines go by `week`, other ones are left as they are
+    pub fn with_week(self, week: Week) -> TimelineType {
         match self {
-            TimelineType::Daily => 1,
-            TimelineType::Weekly => 7,
-            TimelineType::Monthly => 31,
-            TimelineType::Yearly => 365,
+            TimelineType::Weekly(_) => TimelineType::Weekly(week),
+            kind => kind,
         }
     }
 
-    fn duration(&self) -> Duration {
+    // keys of the timeline in the json
+    fn label(&self, start: &NaiveDateTime) -> String {
         match self {
-            TimelineType::Daily => Duration::days(1),
-            TimelineType::Weekly => Duration::weeks(1),
-            TimelineType::Monthly => Duration::days(31),
-            TimelineType::Yearly => Duration::days(365),
+            TimelineType::Weekly(week) if week.iso => week.label(start),
+            _ => start.format("%Y-%m-%dT%H:%M:%S").to_string(),
         }
     }
 
     fn start_of(&self, date: &NaiveDateTime) -> NaiveDateTime {
+        let day = date.date();
+
         match self {
-            TimelineType::Daily => date.sub(Duration::seconds(i64::from(
-                date.num_seconds_from_midnight(),
-            ))),
-            TimelineType::Weekly => date
-                .sub(Duration::seconds(i64::from(
-                    date.num_seconds_from_midnight(),
-                )))
-                .sub(Duration::days(i64::from(
-                    date.weekday().num_days_from_monday(),
-                ))),
-            TimelineType::Monthly => NaiveDateTime::parse_from_str(
-                format!("{}-{:02}-01T00:00:00", date.year(), date.month()).as_str(),
-                "%Y-%m-%dT%H:%M:%S",
-            )
-            .expect("fail to calculate beginning of the month"),
-            TimelineType::Yearly => NaiveDateTime::parse_from_str(
-                format!(
-                    "{}-01-01T00:00:00",
-                    if date.month() == 12 {
-                        date.year() + 1
-                    } else {
-                        date.year()
-                    },
-                )
-                .as_str(),
-                "%Y-%m-%dT%H:%M:%S",
-            )
-            .expect("fail to calculate beginning of the year"),
-        }
-    }
-}
-
-impl string::ToString for TimelineType {
-    fn to_string(&self) -> String {
+            TimelineType::Hourly => return day.and_hms_opt(date.hour(), 0, 0).unwrap(),
+            TimelineType::Interval(interval) => return interval.start_of(date),
+            TimelineType::Daily => day,
+            TimelineType::Weekly(week) => day.week(week.start).first_day(),
+            TimelineType::Monthly => NaiveDate::from_ymd_opt(day.year(), day.month(), 1)
+                .expect("fail to calculate beginning of the month"),
+            TimelineType::Quarterly => {
+                NaiveDate::from_ymd_opt(day.year(), (day.month() - 1) / 3 * 3 + 1, 1)
+                    .expect("fail to calculate beginning of the quarter")
+            }
+            TimelineType::Yearly => NaiveDate::from_ymd_opt(day.year(), 1, 1)
+                .expect("fail to calculate beginning of the year"),
+        }
+        .and_time(NaiveTime::MIN)
+    }
+
+    // beginning of the period after the one starting at `start`, months and years are as long
+    // as the calendar says. `None` once it's past the range of dates
+    fn next(&self, start: &NaiveDateTime) -> Option<NaiveDateTime> {
+        let months = |n| start.checked_add_months(Months::new(n));
+
         match self {
-            TimelineType::Daily => String::from("daily"),
-            TimelineType::Weekly => String::from("weekly"),
-            TimelineType::Monthly => String::from("monthly"),
-            TimelineType::Yearly => String::from("yearly"),
+            TimelineType::Hourly => start.checked_add_signed(Duration::hours(1)),
+            TimelineType::Daily => start.checked_add_signed(Duration::days(1)),
+            TimelineType::Weekly(_) => start.checked_add_signed(Duration::weeks(1)),
+            TimelineType::Monthly => months(1),
+            TimelineType::Quarterly => months(3),
+            TimelineType::Yearly => months(12),
+            TimelineType::Interval(interval) => start.checked_add_signed(interval.length),
         }
     }
+
+    // consecutive periods from the one holding `first` up to the one holding `last`. the first
+    // and the last of them only count the days the conversation went on for, a period that would
+    // end past the range of dates ends with it and is the last one
+    pub fn periods(&self, first: &NaiveDateTime, last: &NaiveDateTime) -> Vec<Period> {
+        let since = first.date().and_time(NaiveTime::MIN);
+        let until = since
+            .checked_add_signed(Duration::days((last.date() - first.date()).num_days() + 1))
+            .unwrap_or(NaiveDateTime::MAX);
+
+        let mut periods = Vec::new();
+        let mut cursor = self.start_of(first);
+
+        while cursor <= *last {
+            let next = self.next(&cursor);
+            let end = next.unwrap_or(NaiveDateTime::MAX);
+            let covered = end.min(until) - cursor.max(since);
+
+            periods.push(Period {
+                start: cursor,
+                end,
+                days: covered.num_seconds() as f32 / 86_400.0,
+            });
+
+            match next {
+                Some(next) => cursor = next,
+                None => break,
+            }
+        }
+
+        periods
+    }
 }
 
-#[derive(Serialize, Clone, Debug)]
+// `[start, end)` of a timeline, `days` is how many of them are covered by the conversation,
+// a fraction of one for periods shorter than a day
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Period {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub days: f32,
+}
+
+impl fmt::Display for TimelineType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineType::Hourly => "hourly",
+            TimelineType::Daily => "daily",
+            TimelineType::Weekly(_) => "weekly",
+            TimelineType::Monthly => "monthly",
+            TimelineType::Quarterly => "quarterly",
+            TimelineType::Yearly => "yearly",
+            TimelineType::Interval(interval) => return interval.fmt(f),
+        }
+        .fmt(f)
+    }
+}
+
+// named periods keep the spelling they always had in the json, `Monthly`, intervals are
+// written as they're passed, `6h`
+impl Serialize for TimelineType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            TimelineType::Hourly => serializer.serialize_str("Hourly"),
+            TimelineType::Daily => serializer.serialize_str("Daily"),
+            TimelineType::Weekly(_) => serializer.serialize_str("Weekly"),
+            TimelineType::Monthly => serializer.serialize_str("Monthly"),
+            TimelineType::Quarterly => serializer.serialize_str("Quarterly"),
+            TimelineType::Yearly => serializer.serialize_str("Yearly"),
+            TimelineType::Interval(interval) => serializer.collect_str(interval),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
 pub struct Stats<T> {
-    messages: T,
-    words: T,
-    letters: T,
+    pub messages: T,
+    pub words: T,
+    pub letters: T,
 }
 
 impl Stats<f32> {
-    fn calc_average(cnv: &Conversation, period: TimelineType) -> Self {
-        let period = period.as_days() as f32;
-
-        let messages = cnv.count() as f32 / period;
-        let words = cnv.words() as f32 / period;
-        let letters = cnv.letters() as f32 / period;
+    fn calc_average(total: &Stats<usize>, days: f32) -> Self {
+        let messages = total.messages as f32 / days;
+        let words = total.words as f32 / days;
+        let letters = total.letters as f32 / days;
 
         Self {
             messages,
@@ -114,72 +293,288 @@ impl Stats<usize> {
             letters: cnv.letters(),
         }
     }
+
+    pub fn add(&mut self, message: &Message) {
+        let (words, letters) = tally(&message.text);
+
+        self.messages += 1;
+        self.words += words;
+        self.letters += letters;
+    }
+
+    pub fn merge(&mut self, other: &Stats<usize>) {
+        self.messages += other.messages;
+        self.words += other.words;
+        self.letters += other.letters;
+    }
+}
+
+// words and letters of a text, only ascii text is taken into account
+fn tally(text: &str) -> (usize, usize) {
+    text.split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_ascii()).count())
+        .filter(|letters| *letters > 0)
+        .fold((0, 0), |(words, letters), n| (words + 1, letters + n))
 }
 
-pub type Frequency = HashMap<String, u32>;
-pub type DateTimeHashMap<T> = HashMap<NaiveDateTime, T>;
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct MediaStats {
+    count: usize,
+    bytes: u64,
+}
 
-pub type TimelineMap = DateTimeHashMap<Conversation>;
+impl MediaStats {
+    pub fn add(&mut self, message: &Message) {
+        self.count += 1;
+        self.bytes += message
+            .attachment
+            .as_ref()
+            .and_then(|a| a.size)
+            .unwrap_or_default();
+    }
+}
+
+// bytes are only counted for attachments found in the export
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Media {
+    total: MediaStats,
+    #[serde(serialize_with = "by_activity")]
+    kinds: HashMap<String, MediaStats>,
+    #[serde(serialize_with = "by_activity")]
+    participants: HashMap<String, MediaStats>,
+}
+
+impl Media {
+    // messages that don't stand for a file are ignored
+    pub fn add(&mut self, message: &Message) {
+        if !message.is_media() {
+            return;
+        }
+
+        self.total.add(message);
+        self.kinds
+            .entry(message.kind.name().to_string())
+            .or_default()
+            .add(message);
+        self.participants
+            .entry(message.author.clone())
+            .or_default()
+            .add(message);
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct EditStats {
+    count: usize,
+    // share of messages that got edited
+    rate: f32,
+    #[serde(skip)]
+    messages: usize,
+}
+
+impl EditStats {
+    pub fn add(&mut self, message: &Message) {
+        self.messages += 1;
+        if message.edited {
+            self.count += 1;
+        }
+
+        self.rate = self.count as f32 / self.messages as f32;
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Edits {
+    total: EditStats,
+    #[serde(serialize_with = "by_activity")]
+    participants: HashMap<String, EditStats>,
+}
+
+impl Edits {
+    pub fn add(&mut self, message: &Message) {
+        self.total.add(message);
+        self.participants
+            .entry(message.author.clone())
+            .or_default()
+            .add(message);
+    }
+}
+
+// how much of what's counted someone stands for, participants are listed from the most to the
+// least active one
+pub trait Activity {
+    fn activity(&self) -> usize;
+}
+
+impl Activity for MediaStats {
+    fn activity(&self) -> usize {
+        self.count
+    }
+}
+
+impl Activity for EditStats {
+    fn activity(&self) -> usize {
+        self.messages
+    }
+}
+
+impl Activity for ParticipantStats {
+    fn activity(&self) -> usize {
+        self.total.messages
+    }
+}
+
+impl Activity for Frequency {
+    fn activity(&self) -> usize {
+        self.values().map(|n| *n as usize).sum()
+    }
+}
+
+// ties are broken by name so the order is the same on every run
+pub fn by_activity<T, S>(
+    map: &HashMap<String, T>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    T: Activity + Serialize,
+    S: Serializer,
+{
+    let mut entries: Vec<(&String, &T)> = map.iter().collect();
+    entries.sort_by_key(|(name, value)| (Reverse(value.activity()), *name));
+
+    serializer.collect_map(entries)
+}
+
+// hours are keyed `00h` to `23h`, so they're kept in order
+pub type Frequency = BTreeMap<String, u32>;
+pub type DateTimeMap<T> = BTreeMap<NaiveDateTime, T>;
+
+pub type TimelineMap = DateTimeMap<Conversation>;
 
 #[derive(Serialize, Clone, Debug)]
 pub struct ParticipantStats {
-    total: Stats<usize>,
-    average: Stats<f32>,
+    pub total: Stats<usize>,
+    pub average: Stats<f32>,
 }
 
 pub type ParticipantMap = HashMap<String, ParticipantStats>;
 
 #[derive(Serialize, Clone, Debug)]
 pub struct TimelineStats {
-    total: Stats<usize>,
-    average: Stats<f32>,
+    pub total: Stats<usize>,
+    pub average: Stats<f32>,
 
-    period: TimelineType,
-    participants: ParticipantMap,
+    pub period: TimelineType,
+    pub days: f32,
+    #[serde(serialize_with = "by_activity")]
+    pub participants: ParticipantMap,
 }
 
-#[derive(Serialize, Clone, Debug)]
-pub struct Timeline(DateTimeHashMap<TimelineStats>);
+impl TimelineStats {
+    pub fn new(
+        total: Stats<usize>,
+        participants: HashMap<String, Stats<usize>>,
+        period: TimelineType,
+        days: f32,
+    ) -> Self {
+        let average = Stats::<f32>::calc_average(&total, days);
+
+        let participants = participants
+            .into_iter()
+            .map(|(p, total)| {
+                let average = Stats::<f32>::calc_average(&total, days);
+                (p, ParticipantStats { total, average })
+            })
+            .collect();
 
-impl Into<DateTimeHashMap<TimelineStats>> for Timeline {
-    fn into(self) -> DateTimeHashMap<TimelineStats> {
-        self.0
+        TimelineStats {
+            total,
+            average,
+            participants,
+            period,
+            days,
+        }
     }
 }
 
-impl Timeline {
-    fn new(src: TimelineMap, period: TimelineType) -> Self {
-        let mut map = HashMap::new();
+#[derive(Clone, Debug)]
+pub struct Timeline(DateTimeMap<TimelineStats>);
+
+// periods are keyed by when they start, or by their iso week if they're weeks labelled so, and
+// come in chronological order
+impl Serialize for Timeline {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(start, stats)| (stats.period.label(start), stats)),
+        )
+    }
+}
+
+impl From<Timeline> for DateTimeMap<TimelineStats> {
+    fn from(timeline: Timeline) -> Self {
+        timeline.0
+    }
+}
+
+impl From<DateTimeMap<TimelineStats>> for Timeline {
+    fn from(map: DateTimeMap<TimelineStats>) -> Self {
+        Timeline(map)
+    }
+}
 
-        for (dt, cnv) in src {
-            let total = Stats::<usize>::calc_total(&cnv);
-            let average = Stats::<f32>::calc_average(&cnv, period);
+impl Timeline {
+    // periods in chronological order, labelled as in the json
+    pub fn iter(&self) -> impl Iterator<Item = (String, &TimelineStats)> {
+        self.0
+            .iter()
+            .map(|(start, stats)| (stats.period.label(start), stats))
+    }
 
-            let mut participants: ParticipantMap = HashMap::new();
+    // number of messages in each period
+    pub fn messages(&self) -> Vec<(String, usize)> {
+        self.iter()
+            .map(|(label, stats)| (label, stats.total.messages))
+            .collect()
+    }
 
-            for p in cnv.participants() {
-                let p_cnv = cnv.by_author(p.to_string());
+    // the same split by participant
+    pub fn messages_per_participant(&self) -> Vec<(String, HashMap<String, usize>)> {
+        self.iter()
+            .map(|(label, stats)| {
+                let participants = stats
+                    .participants
+                    .iter()
+                    .map(|(p, s)| (p.to_string(), s.total.messages))
+                    .collect();
+
+                (label, participants)
+            })
+            .collect()
+    }
 
-                let p_total = Stats::<usize>::calc_total(&p_cnv);
-                let p_average = Stats::<f32>::calc_average(&p_cnv, period);
+    fn new(src: Vec<(Period, Conversation)>, kind: TimelineType) -> Self {
+        let mut map = BTreeMap::new();
 
-                participants.insert(
-                    p.to_string(),
-                    ParticipantStats {
-                        total: p_total,
-                        average: p_average,
-                    },
-                );
-            }
+        for (period, cnv) in src {
+            let participants = cnv
+                .participants()
+                .iter()
+                .map(|p| {
+                    let total = Stats::<usize>::calc_total(&cnv.by_author(p.to_string()));
+                    (p.to_string(), total)
+                })
+                .collect();
 
             map.insert(
-                dt,
-                TimelineStats {
-                    total,
-                    average,
+                period.start,
+                TimelineStats::new(
+                    Stats::<usize>::calc_total(&cnv),
                     participants,
-                    period: period,
-                },
+                    kind,
+                    period.days,
+                ),
             );
         }
 
@@ -187,55 +582,224 @@ impl Timeline {
     }
 }
 
-#[derive(Debug, Clone)]
+// layout, timestamp dialect and language are detected from the contents unless set
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub format: Option<Format>,
+    pub dialect: Option<Dialect>,
+    pub locale: Option<&'static Locale>,
+    // zone of the phone that made the export, utc unless set
+    pub zone: Option<Tz>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConflictKind {
+    Edited,
+    Deleted,
+    Changed,
+}
+
+// two versions of the same message found in overlapping exports, the one from the later export
+// is kept
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub kept: Message,
+    pub dropped: Message,
+}
+
+impl Conflict {
+    fn new(kept: Message, dropped: Message) -> Conflict {
+        let kind = if kept.kind == MessageType::Deleted || dropped.kind == MessageType::Deleted {
+            ConflictKind::Deleted
+        } else if kept.edited || dropped.edited {
+            ConflictKind::Edited
+        } else {
+            ConflictKind::Changed
+        };
+
+        Conflict {
+            kind,
+            kept,
+            dropped,
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::Edited => "edited",
+            ConflictKind::Deleted => "deleted",
+            ConflictKind::Changed => "changed",
+        };
+
+        write!(
+            f,
+            "{} message of {} at {}, kept {:?} over {:?}",
+            kind, self.kept.author, self.kept.datetime, self.kept.text, self.dropped.text
+        )
+    }
+}
+
+// texts sharing at least half the words of the longer one, what an export that changed a message
+// a little leaves of it
+fn similar(a: &str, b: &str) -> bool {
+    let a: HashSet<&str> = a.split_whitespace().collect();
+    let b: HashSet<&str> = b.split_whitespace().collect();
+
+    a.intersection(&b).count() * 2 >= a.len().max(b.len())
+}
+
+// an edited or deleted message is taken for a version of any other one, anything else only when
+// its text is close enough. timestamps only go down to the minute in some exports, two messages
+// sent within it are told apart that way
+fn is_version(a: &Message, b: &Message) -> bool {
+    a.author == b.author
+        && (a.edited
+            || b.edited
+            || a.kind == MessageType::Deleted
+            || b.kind == MessageType::Deleted
+            || similar(&a.text, &b.text))
+}
+
+// lines up two chronological runs of messages, within each timestamp messages with the same
+// author and text are taken for the same one and the remaining ones for versions of each other
+// where they look like it
+fn merge_runs(
+    ours: Vec<Message>,
+    theirs: Vec<Message>,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<Message> {
+    let mut merged = Vec::with_capacity(ours.len().max(theirs.len()));
+
+    let mut ours = ours.into_iter().peekable();
+    let mut theirs = theirs.into_iter().peekable();
+
+    loop {
+        let datetime = match (ours.peek(), theirs.peek()) {
+            (Some(a), Some(b)) => a.datetime.min(b.datetime),
+            (Some(a), None) => a.datetime,
+            (None, Some(b)) => b.datetime,
+            (None, None) => break,
+        };
+
+        let mut left = Vec::new();
+        while let Some(m) = ours.next_if(|m| m.datetime == datetime) {
+            left.push(m);
+        }
+
+        let mut right = Vec::new();
+        while let Some(m) = theirs.next_if(|m| m.datetime == datetime) {
+            right.push(Some(m));
+        }
+
+        let mut unmatched = Vec::new();
+
+        for m in left {
+            let duplicate = right.iter().position(|r| {
+                r.as_ref()
+                    .is_some_and(|r| r.author == m.author && r.text == m.text)
+            });
+
+            match duplicate {
+                Some(i) => right[i] = None,
+                None => unmatched.push(merged.len()),
+            }
+
+            merged.push(m);
+        }
+
+        for i in unmatched {
+            let version = right
+                .iter()
+                .position(|r| r.as_ref().is_some_and(|r| is_version(r, &merged[i])));
+
+            if let Some(version) = version.and_then(|v| right[v].take()) {
+                let dropped = std::mem::replace(&mut merged[i], version.clone());
+                conflicts.push(Conflict::new(version, dropped));
+            }
+        }
+
+        merged.extend(right.into_iter().flatten());
+    }
+
+    merged
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Conversation {
     messages: Vec<Message>,
+    events: Vec<Message>,
     participants: Vec<String>,
 }
 
+// system messages are kept apart from the others
+impl FromIterator<Message> for Conversation {
+    fn from_iter<I: IntoIterator<Item = Message>>(iter: I) -> Self {
+        let (events, messages) = iter
+            .into_iter()
+            .partition(|m: &Message| m.kind == MessageType::System);
+
+        Conversation::from_messages(messages, events)
+    }
+}
+
 #[allow(dead_code)]
 impl Conversation {
     pub fn from_str(raw: &str) -> Result<Conversation> {
-        lazy_static! {
-            static ref PATTERN:Regex = Regex::new(r"\[(?P<datetime>\d{4}-\d{2}-\d{2},\s\d{2}:\d{2}:\d{2})\]\s(?P<author>.*?):\s(?P<text>.*)").expect("invalid regex");
+        Self::parse(raw, &ParseOptions::default())
+    }
+
+    pub fn parse(raw: &str, options: &ParseOptions) -> Result<Conversation> {
+        Messages::new(raw.as_bytes(), options)?
+            .map(|m| m.map_err(|d| d.error))
+            .collect()
+    }
+
+    pub fn new(messages: Vec<Message>, participants: Vec<String>) -> Conversation {
+        Conversation {
+            messages,
+            events: Vec::new(),
+            participants,
         }
+    }
 
-        let mut messages: Vec<Message> = Vec::new();
+    // for exports that come already split into messages, participants in order of appearance
+    pub fn from_messages(messages: Vec<Message>, events: Vec<Message>) -> Conversation {
         let mut participants: Vec<String> = Vec::new();
 
-        for capture in PATTERN.captures_iter(&raw) {
-            if capture["text"]
-                .contains("Messages to this group are now secured with end-to-end encryption")
-            {
-                continue;
-            }
-
-            let message = match Message::from_str(
-                &capture["datetime"],
-                &capture["author"],
-                &capture["text"].trim(),
-            ) {
-                Ok(message) => message,
-                Err(e) => return Err(e),
-            };
+        for message in messages.iter() {
             if !participants.contains(&message.author) {
                 participants.push(message.author.clone());
             }
-
-            messages.push(message);
         }
 
-        Ok(Conversation {
+        Conversation {
             messages,
+            events,
             participants,
-        })
+        }
     }
 
-    pub fn new(messages: Vec<Message>, participants: Vec<String>) -> Conversation {
-        Conversation {
-            messages,
-            participants,
+    // takes in an export that overlaps with this one, messages found in both are only kept once
+    // and where they differ the version of `other` wins
+    pub fn merge(&mut self, other: Conversation) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+
+        let messages = std::mem::take(&mut self.messages);
+        self.messages = merge_runs(messages, other.messages, &mut conflicts);
+
+        let events = std::mem::take(&mut self.events);
+        self.events = merge_runs(events, other.events, &mut conflicts);
+
+        for p in other.participants {
+            if !self.participants.contains(&p) {
+                self.participants.push(p);
+            }
         }
+
+        conflicts
     }
 
     pub fn first(&self) -> Option<&Message> {
@@ -256,7 +820,7 @@ impl Conversation {
             None => return Err(MessageError(MessageErrorKind::EmptyMessage)),
         };
 
-        Ok(last.datetime.sub(first.datetime))
+        Ok(last.datetime - first.datetime)
     }
 
     pub fn count(&self) -> usize {
@@ -281,17 +845,11 @@ impl Conversation {
     }
 
     pub fn words(&self) -> usize {
-        self.combine().split_whitespace().count()
+        self.messages.iter().map(|m| tally(&m.text).0).sum()
     }
 
     pub fn letters(&self) -> usize {
-        let words: Vec<String> = self
-            .combine()
-            .split_whitespace()
-            .map(|word| String::from(word))
-            .collect();
-
-        words.join("").len()
+        self.messages.iter().map(|m| tally(&m.text).1).sum()
     }
 
     // average letters and words per message
@@ -312,6 +870,24 @@ impl Conversation {
         (w / c, l / c)
     }
 
+    pub fn messages(&self) -> &Vec<Message> {
+        &self.messages
+    }
+
+    // messages along with the system ones in chronological order
+    pub fn into_messages(self) -> Vec<Message> {
+        let mut messages = self.messages;
+        messages.extend(self.events);
+        messages.sort_by_key(|m| m.datetime);
+
+        messages
+    }
+
+    // system messages in chronological order, these never count towards the stats
+    pub fn events(&self) -> &Vec<Message> {
+        &self.events
+    }
+
     pub fn participants(&self) -> &Vec<String> {
         &self.participants
     }
@@ -324,6 +900,7 @@ impl Conversation {
                 .into_iter()
                 .filter(|m| m.author.eq(&author))
                 .collect(),
+            events: Vec::new(),
             participants: vec![author],
         }
     }
@@ -334,18 +911,51 @@ impl Conversation {
                 .messages
                 .clone()
                 .into_iter()
-                .filter(|m| m.datetime >= start && m.datetime < end)
+                .filter(|m| m.datetime.naive_local() >= start && m.datetime.naive_local() < end)
+                .collect(),
+            events: self
+                .events
+                .iter()
+                .filter(|m| m.datetime.naive_local() >= start && m.datetime.naive_local() < end)
+                .cloned()
                 .collect(),
             participants: self.participants().clone(),
         }
     }
 
+    // fills in the size of attachments from the files shipped along with the chat
+    pub fn resolve_attachments(&mut self, files: &HashMap<String, u64>) {
+        for message in self.messages.iter_mut() {
+            message.resolve_attachment(files);
+        }
+    }
+
+    pub fn media(&self) -> Media {
+        let mut media = Media::default();
+
+        for m in self.messages.iter() {
+            media.add(m);
+        }
+
+        media
+    }
+
+    pub fn edits(&self) -> Edits {
+        let mut edits = Edits::default();
+
+        for m in self.messages.iter() {
+            edits.add(m);
+        }
+
+        edits
+    }
+
     pub fn emojis(&self) -> Emojis {
         emoji::count(&self.combine_raw())
     }
 
     pub fn frequency(&self) -> Frequency {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         for n in 0..24 {
             map.insert(format!("{:02}h", n), 0);
         }
@@ -360,31 +970,25 @@ impl Conversation {
         map
     }
 
-    fn timeline_map(&self, kind: TimelineType) -> TimelineMap {
-        let first = self.first().unwrap().datetime;
-        let last = self.last().unwrap().datetime;
-
-        let mut cursor = kind.start_of(&first);
-
-        let mut timeline = HashMap::new();
-
-        loop {
-            if cursor > last {
-                break;
-            }
-
-            let next = kind.start_of(&cursor.add(kind.duration()));
+    fn periods(&self, kind: TimelineType) -> Vec<(Period, Conversation)> {
+        let first = self.first().unwrap().datetime.naive_local();
+        let last = self.last().unwrap().datetime.naive_local();
 
-            timeline.insert(cursor, self.by_range(cursor, next));
-
-            cursor = next;
-        }
+        kind.periods(&first, &last)
+            .into_iter()
+            .map(|period| (period, self.by_range(period.start, period.end)))
+            .collect()
+    }
 
-        timeline
+    fn timeline_map(&self, kind: TimelineType) -> TimelineMap {
+        self.periods(kind)
+            .into_iter()
+            .map(|(period, cnv)| (period.start, cnv))
+            .collect()
     }
 
     pub fn timeline(&self, kind: TimelineType) -> Timeline {
-        Timeline::new(self.timeline_map(kind), kind)
+        Timeline::new(self.periods(kind), kind)
     }
 }
 
@@ -409,6 +1013,167 @@ mod tests {
         assert_eq!(c.participants.len(), 2);
         assert_eq!(c.participants, vec!["Foo", "Bar Baz"]);
     }
+    #[test]
+    fn from_str_parses_android_messages() {
+        let ios = Conversation::from_str(
+            r"
+[2019-12-31, 22:15:00] Foo: Hey! 💩
+[2020-01-01, 00:01:00] Bar Baz: heyyyyyyy, 'sup
+",
+        )
+        .unwrap();
+        let android = Conversation::from_str(
+            r"
+12/31/19, 10:15 PM - Foo: Hey! 💩
+1/1/20, 12:01 AM - Bar Baz: heyyyyyyy, 'sup
+",
+        )
+        .unwrap();
+
+        assert_eq!(android.messages, ios.messages);
+        assert_eq!(android.participants, vec!["Foo", "Bar Baz"]);
+    }
+
+    #[test]
+    fn from_str_detects_dialect() {
+        let c = Conversation::from_str(
+            r"
+[11.09.19, 23:57:17] Foo: Hallo
+[12.09.19, 00:01:02] Bar: Servus
+[13.09.19, 08:00:00] Foo: Moin
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            format!("{}", c.first().unwrap().datetime.naive_local()),
+            "2019-09-11 23:57:17"
+        );
+        assert_eq!(
+            format!("{}", c.last().unwrap().datetime.naive_local()),
+            "2019-09-13 08:00:00"
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_ambiguous_dialect() {
+        let raw = "01/02/2019, 10:00 - Foo: Bar\n03/04/2019, 10:00 - Bar: Foo";
+        assert!(Conversation::from_str(raw).is_err());
+
+        let c = Conversation::parse(
+            raw,
+            &ParseOptions {
+                dialect: Some(Dialect::MonthDayYear),
+                ..ParseOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            format!("{}", c.last().unwrap().datetime.naive_local()),
+            "2019-03-04 10:00:00"
+        );
+    }
+
+    #[test]
+    fn from_str_keeps_multiline_messages() {
+        let c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: Dear all,
+
+here is the plan:
+[see attached] and [2019-09-11] for the date
+[2019-09-11, 01:58:17] Bar: ok
+",
+        )
+        .unwrap();
+
+        assert_eq!(c.count(), 2);
+        assert_eq!(
+            c.first().unwrap().text,
+            "Dear all,\n\nhere is the plan:\n[see attached] and [2019-09-11] for the date"
+        );
+        assert_eq!(c.last().unwrap().text, "ok");
+        assert_eq!(c.first().unwrap().words().len(), 13);
+    }
+
+    #[test]
+    fn from_str_parses_android_system_messages() {
+        let c = Conversation::from_str(
+            r#"
+9/13/19, 1:57 AM - Foo: Hey!
+9/13/19, 1:58 AM - Foo added Bar
+9/13/19, 1:59 AM - Bar changed the subject from "a: b" to "c"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(c.participants, vec!["Foo"]);
+        assert_eq!(c.events().len(), 2);
+        assert_eq!(c.events()[1].author, "");
+        assert_eq!(
+            c.events()[1].text,
+            r#"Bar changed the subject from "a: b" to "c""#
+        );
+        assert!(c.events()[1].event.is_some());
+    }
+
+    #[test]
+    fn from_str_keeps_multiline_android_messages() {
+        let c = Conversation::from_str(
+            r"
+12/31/19, 10:15 PM - Messages to this group are now secured with end-to-end encryption.
+12/31/19, 10:15 PM - Foo: first line
+second line: with a colon
+12/31/19, 10:16 PM - Foo added Bar
+1/1/20, 12:01 AM - Bar: hey
+",
+        )
+        .unwrap();
+
+        assert_eq!(c.count(), 2);
+        assert_eq!(
+            c.first().unwrap().text,
+            "first line\nsecond line: with a colon"
+        );
+        assert_eq!(c.participants(), &vec!["Foo", "Bar"]);
+    }
+
+    #[test]
+    fn from_str_detects_locale() {
+        let c = Conversation::from_str(
+            r"
+[13.09.19, 01:57:17] Gruppe: ‎Foo hat Bar hinzugefügt
+[13.09.19, 01:58:17] Foo: ‎Bild weggelassen
+[13.09.19, 01:59:17] Bar: ‎Audio weggelassen
+[13.09.19, 02:00:00] Bar: Hallo
+",
+        )
+        .unwrap();
+
+        assert_eq!(c.count(), 3);
+        assert_eq!(c.events().len(), 1);
+        assert_eq!(c.messages[0].kind, MessageType::Image);
+        assert_eq!(c.messages[1].kind, MessageType::Audio);
+        assert_eq!(c.messages[2].kind, MessageType::Text);
+    }
+
+    #[test]
+    fn parse_uses_locale() {
+        let raw =
+            "[13.09.19, 01:58:17] Foo: ‎image omitted\n[13.09.19, 01:59:17] Bar: ‎imagen omitida";
+        let c = Conversation::parse(
+            raw,
+            &ParseOptions {
+                locale: Locale::find("es"),
+                ..ParseOptions::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(c.messages[0].kind, MessageType::Text);
+        assert_eq!(c.messages[1].kind, MessageType::Image);
+    }
+
     #[test]
     fn first_works() {
         let c = Conversation::from_str(MOCK).unwrap();
@@ -469,12 +1234,193 @@ mod tests {
         assert_eq!(c1.participants, vec!["Bar Baz"]);
     }
 
+    #[test]
+    fn into_messages_works() {
+        let c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: Hey
+[2019-09-11, 01:58:17] Group: ‎Foo added Bar
+[2019-09-11, 01:59:17] Bar: hi
+",
+        )
+        .unwrap();
+
+        let kinds: Vec<MessageType> = c.into_messages().into_iter().map(|m| m.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![MessageType::Text, MessageType::System, MessageType::Text]
+        );
+    }
+
+    #[test]
+    fn merge_drops_duplicates() {
+        let mut c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: Hey
+[2019-09-11, 01:58:17] Bar: hi
+[2019-09-11, 01:58:17] Bar: hi
+",
+        )
+        .unwrap();
+        let other = Conversation::from_str(
+            r"
+[2019-09-11, 01:58:17] Bar: hi
+[2019-09-11, 01:58:17] Bar: hi
+[2019-09-11, 01:59:17] Group: ‎Foo added Baz
+[2019-09-11, 02:00:00] Baz: hello
+",
+        )
+        .unwrap();
+
+        let conflicts = c.merge(other);
+        let texts: Vec<&str> = c.messages().iter().map(|m| m.text.as_str()).collect();
+
+        assert!(conflicts.is_empty());
+        assert_eq!(texts, vec!["Hey", "hi", "hi", "hello"]);
+        assert_eq!(c.events().len(), 1);
+        assert_eq!(c.participants(), &vec!["Foo", "Bar", "Baz"]);
+    }
+
+    #[test]
+    fn merge_reports_conflicts() {
+        let mut c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: Hey
+[2019-09-11, 01:58:17] Bar: hi
+[2019-09-11, 01:59:17] Foo: bye
+",
+        )
+        .unwrap();
+        let other = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: Hey there ‎<This message was edited>
+[2019-09-11, 01:58:17] Bar: ‎This message was deleted
+[2019-09-11, 01:59:17] Foo: bye
+",
+        )
+        .unwrap();
+
+        let conflicts = c.merge(other);
+        let kinds: Vec<ConflictKind> = conflicts.iter().map(|c| c.kind.clone()).collect();
+
+        assert_eq!(kinds, vec![ConflictKind::Edited, ConflictKind::Deleted]);
+        assert_eq!(conflicts[0].dropped.text, "Hey");
+        assert_eq!(c.count(), 3);
+        assert_eq!(c.first().unwrap().text, "Hey there");
+        assert_eq!(c.messages()[1].kind, MessageType::Deleted);
+    }
+
+    #[test]
+    fn merge_keeps_different_messages_of_the_same_minute() {
+        let mut c = Conversation::from_str(
+            r"
+9/13/19, 1:57 AM - Foo: Hey
+9/13/19, 1:58 AM - Bar: are you there?
+",
+        )
+        .unwrap();
+        let other = Conversation::from_str(
+            r"
+9/13/19, 1:57 AM - Foo: Hey
+9/13/19, 1:58 AM - Bar: lunch at noon
+9/13/19, 1:58 AM - Bar: are you there ?
+",
+        )
+        .unwrap();
+
+        let conflicts = c.merge(other);
+        let texts: Vec<&str> = c.messages().iter().map(|m| m.text.as_str()).collect();
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::Changed);
+        assert_eq!(texts, vec!["Hey", "are you there ?", "lunch at noon"]);
+    }
+
     #[test]
     fn emojis_works() {
         let c = Conversation::from_str(MOCK).unwrap();
 
         assert_eq!(c.emojis().keys().len(), 1);
-        assert_eq!(c.emojis().keys().nth(0), Some(&String::from("💩")));
+        assert_eq!(c.emojis().keys().next(), Some(&String::from("💩")));
+    }
+
+    #[test]
+    fn media_works() {
+        let mut c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: ‎<attached: 00000012-PHOTO-2019-09-11-01-57-17.jpg>
+[2019-09-11, 01:57:18] Foo: ‎<attached: 00000013-PHOTO-2019-09-11-01-57-18.jpg>
+[2019-09-11, 01:57:19] Bar: ‎<attached: 00000014-AUDIO-2019-09-11-01-57-19.opus>
+[2019-09-11, 01:57:20] Bar: ‎video omitted
+[2019-09-11, 01:57:21] Bar: nice
+",
+        )
+        .unwrap();
+
+        let mut files = HashMap::new();
+        files.insert(String::from("00000012-PHOTO-2019-09-11-01-57-17.jpg"), 100);
+        files.insert(String::from("00000013-PHOTO-2019-09-11-01-57-18.jpg"), 20);
+        files.insert(String::from("00000014-AUDIO-2019-09-11-01-57-19.opus"), 3);
+        c.resolve_attachments(&files);
+
+        let m = c.media();
+        assert_eq!((m.total.count, m.total.bytes), (4, 123));
+        assert_eq!((m.kinds["image"].count, m.kinds["image"].bytes), (2, 120));
+        assert_eq!((m.kinds["video"].count, m.kinds["video"].bytes), (1, 0));
+        assert_eq!(
+            (m.participants["Foo"].count, m.participants["Foo"].bytes),
+            (2, 120)
+        );
+        assert_eq!(
+            (m.participants["Bar"].count, m.participants["Bar"].bytes),
+            (2, 3)
+        );
+    }
+
+    #[test]
+    fn edits_works() {
+        let c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: Hey ‎<This message was edited>
+[2019-09-11, 01:57:18] Foo: there
+[2019-09-11, 01:57:19] Bar: hi ‎<This message was edited>
+[2019-09-11, 01:57:20] Foo: ok
+",
+        )
+        .unwrap();
+
+        assert_eq!(c.average(), (1.0, 3.0));
+
+        let e = c.edits();
+        assert_eq!((e.total.count, e.total.rate), (2, 0.5));
+        assert_eq!(e.participants["Foo"].count, 1);
+        assert_eq!(e.participants["Bar"].rate, 1.0);
+    }
+
+    #[test]
+    fn participants_are_serialized_by_activity() {
+        let c = Conversation::from_str(
+            r"
+[2019-09-11, 01:57:17] Foo: ‎image omitted
+[2019-09-11, 01:57:18] Bar: ‎video omitted
+[2019-09-11, 01:57:19] Bar: ‎image omitted
+[2019-09-11, 01:57:20] Baz: ‎audio omitted
+[2019-09-11, 01:57:21] Foo: nice
+",
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&c.media()).unwrap();
+        let at = |name: &str| json.find(name).unwrap();
+
+        assert!(at(r#""image""#) < at(r#""audio""#));
+        assert!(at(r#""audio""#) < at(r#""video""#));
+        assert!(at(r#""Bar""#) < at(r#""Baz""#));
+        assert!(at(r#""Baz""#) < at(r#""Foo""#));
+
+        let json = serde_json::to_string(&c.edits()).unwrap();
+        assert!(json.find(r#""Bar""#) < json.find(r#""Foo""#));
+        assert!(json.find(r#""Foo""#) < json.find(r#""Baz""#));
     }
 
     #[test]
@@ -559,7 +1505,7 @@ mod tests {
 [2001-01-18, 23:59:59] Kendrick: Bitch, be humble.";
 
         let c = Conversation::from_str(mock_for_weekly).unwrap();
-        let t = c.timeline_map(TimelineType::Weekly);
+        let t = c.timeline_map(TimelineType::Weekly(Week::default()));
 
         assert_eq!(t.keys().len(), 3);
 
@@ -651,9 +1597,253 @@ mod tests {
         let c = Conversation::from_str(mock_for_yearly).unwrap();
         let t = c.timeline(TimelineType::Yearly);
 
-        let m: DateTimeHashMap<_> = t.clone().into();
+        let m: DateTimeMap<_> = t.clone().into();
 
         assert_eq!(m.len(), 8);
         // TODO: add more cases
     }
+
+    #[test]
+    fn periods_follow_the_calendar() {
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap();
+
+        let years = TimelineType::Yearly.periods(&at("2003-12-31 23:59"), &at("2004-12-01 10:00"));
+        assert_eq!(years.len(), 2);
+        assert_eq!(years[0].start, at("2003-01-01 00:00"));
+        assert_eq!(years[1].start, at("2004-01-01 00:00"));
+        assert_eq!(years[1].end, at("2005-01-01 00:00"));
+
+        let months =
+            TimelineType::Monthly.periods(&at("2004-01-31 10:00"), &at("2004-03-01 10:00"));
+        let starts: Vec<_> = months.iter().map(|p| p.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                at("2004-01-01 00:00"),
+                at("2004-02-01 00:00"),
+                at("2004-03-01 00:00")
+            ]
+        );
+    }
+
+    #[test]
+    fn periods_count_the_days_covered() {
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap();
+
+        let days = |kind: TimelineType, first, last| -> Vec<f32> {
+            kind.periods(&at(first), &at(last))
+                .iter()
+                .map(|p| p.days)
+                .collect()
+        };
+
+        assert_eq!(
+            days(
+                TimelineType::Monthly,
+                "2003-01-01 10:00",
+                "2004-03-31 10:00"
+            )[12..],
+            [31.0, 29.0, 31.0]
+        );
+        assert_eq!(
+            days(
+                TimelineType::Monthly,
+                "2001-01-20 10:00",
+                "2001-02-10 10:00"
+            ),
+            vec![12.0, 10.0]
+        );
+        assert_eq!(
+            days(TimelineType::Yearly, "2003-01-01 10:00", "2004-12-31 10:00"),
+            vec![365.0, 366.0]
+        );
+        assert_eq!(
+            days(
+                TimelineType::Weekly(Week::default()),
+                "2001-01-03 10:00",
+                "2001-01-03 11:00"
+            ),
+            vec![1.0]
+        );
+    }
+
+    #[test]
+    fn timeline_averages_over_days_covered() {
+        let c = Conversation::from_str(
+            "
+[2001-02-01, 00:34:56] Foo: one
+[2001-02-14, 10:34:56] Foo: two
+[2001-02-28, 23:59:59] Bar: three
+[2001-03-01, 00:00:00] Bar: four
+",
+        )
+        .unwrap();
+
+        let t: DateTimeMap<_> = c.timeline(TimelineType::Monthly).into();
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S").unwrap();
+
+        let february = &t[&at("2001-02-01T00:00:00")];
+        assert_eq!(february.days, 28.0);
+        assert_eq!(february.average.messages, 3.0 / 28.0);
+
+        let march = &t[&at("2001-03-01T00:00:00")];
+        assert_eq!(march.days, 1.0);
+        assert_eq!(march.average.messages, 1.0);
+        assert_eq!(march.participants["Bar"].average.messages, 1.0);
+    }
+
+    #[test]
+    fn timeline_type_from_name_works() {
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap();
+
+        assert_eq!(
+            TimelineType::from_name("quarterly", None)
+                .unwrap()
+                .to_string(),
+            "quarterly"
+        );
+        assert_eq!(
+            TimelineType::from_name("6h", None).unwrap().to_string(),
+            "6h"
+        );
+        assert_eq!(
+            TimelineType::from_name("14d", Some(at("2019-09-11 06:00")))
+                .unwrap()
+                .to_string(),
+            "2w"
+        );
+        assert!(TimelineType::from_name("0d", None).is_none());
+        assert!(TimelineType::from_name("6m", None).is_none());
+        assert!(TimelineType::from_name("daily", Some(at("2019-09-11 06:00"))).is_none());
+        assert!(TimelineType::from_name("6h", Some(at("2019-09-11 06:30"))).is_none());
+    }
+
+    #[test]
+    fn intervals_past_the_range_of_dates_work() {
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap();
+
+        assert!(TimelineType::from_name("99999999999999h", None).is_none());
+
+        let kind = TimelineType::from_name("3000000000h", None).unwrap();
+        let periods = kind.periods(&at("2019-09-11 01:57"), &at("2019-09-13 22:15"));
+        assert_eq!(periods.len(), 1);
+        assert_eq!(periods[0].start, at("2019-09-11 00:00"));
+        assert_eq!(periods[0].end, NaiveDateTime::MAX);
+        assert_eq!(periods[0].days, 3.0);
+
+        let kind = TimelineType::from_name("3000000000h", Some(at("2030-01-01 00:00"))).unwrap();
+        assert_eq!(
+            kind.periods(&at("2019-09-11 01:57"), &at("2019-09-13 22:15"))
+                .len(),
+            1
+        );
+
+        let c = Conversation::from_str(MOCK).unwrap();
+        assert_eq!(c.timeline(kind).messages().len(), 1);
+    }
+
+    #[test]
+    fn periods_of_other_lengths_work() {
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap();
+        let starts = |kind: TimelineType, first, last| -> Vec<NaiveDateTime> {
+            kind.periods(&at(first), &at(last))
+                .iter()
+                .map(|p| p.start)
+                .collect()
+        };
+
+        let hours = TimelineType::Hourly.periods(&at("2019-09-11 01:57"), &at("2019-09-11 03:00"));
+        assert_eq!(hours.len(), 3);
+        assert_eq!(hours[2].start, at("2019-09-11 03:00"));
+        assert_eq!(hours[2].days, 1.0 / 24.0);
+
+        assert_eq!(
+            starts(
+                TimelineType::Quarterly,
+                "2019-02-11 01:57",
+                "2019-12-31 23:59"
+            ),
+            vec![
+                at("2019-01-01 00:00"),
+                at("2019-04-01 00:00"),
+                at("2019-07-01 00:00"),
+                at("2019-10-01 00:00")
+            ]
+        );
+        assert_eq!(
+            starts(
+                TimelineType::from_name("6h", None).unwrap(),
+                "2019-09-11 13:57",
+                "2019-09-12 01:00"
+            ),
+            vec![
+                at("2019-09-11 12:00"),
+                at("2019-09-11 18:00"),
+                at("2019-09-12 00:00")
+            ]
+        );
+
+        let anchored = TimelineType::from_name("14d", Some(at("2019-09-20 06:00"))).unwrap();
+        let periods = anchored.periods(&at("2019-09-11 01:57"), &at("2019-09-21 10:00"));
+        assert_eq!(periods[0].start, at("2019-09-06 06:00"));
+        assert_eq!(periods[1].start, at("2019-09-20 06:00"));
+        // the first period is counted from the first day, the last one up to the end of the last
+        assert_eq!(periods[0].days, 9.25);
+        assert_eq!(periods[1].days, 1.75);
+    }
+
+    #[test]
+    fn periods_start_weeks_on_the_day_set() {
+        let at = |raw| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap();
+        let start = |day| {
+            let week = Week {
+                start: day,
+                iso: false,
+            };
+            TimelineType::Weekly(week).periods(&at("2019-09-11 01:57"), &at("2019-09-11 01:57"))[0]
+                .start
+        };
+
+        assert_eq!(start(Weekday::Mon), at("2019-09-09 00:00"));
+        assert_eq!(start(Weekday::Sun), at("2019-09-08 00:00"));
+        assert_eq!(start(Weekday::Sat), at("2019-09-07 00:00"));
+        assert_eq!(start(Weekday::Wed), at("2019-09-11 00:00"));
+    }
+
+    #[test]
+    fn timeline_labels_iso_weeks() {
+        let c = Conversation::from_str(
+            "
+[2019-09-08, 01:57:17] Foo: sunday
+[2019-09-14, 13:01:00] Bar: saturday
+[2019-12-30, 10:00:00] Foo: monday
+",
+        )
+        .unwrap();
+
+        let keys = |week| -> Vec<String> {
+            let timeline = c.timeline(TimelineType::Weekly(Week::default()).with_week(week));
+            let json = serde_json::to_value(timeline).unwrap();
+            let mut keys: Vec<String> = json.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            keys
+        };
+
+        let iso = keys(Week {
+            start: Weekday::Mon,
+            iso: true,
+        });
+        assert_eq!(iso.len(), 18);
+        assert_eq!(iso[0], "2019-W36");
+        assert_eq!(iso[17], "2020-W01");
+
+        let sunday = keys(Week {
+            start: Weekday::Sun,
+            iso: true,
+        });
+        assert_eq!(sunday[..2], ["2019-W37", "2019-W38"]);
+
+        let plain = keys(Week::default());
+        assert_eq!(plain[0], "2019-09-02T00:00:00");
+    }
 }
@@ -0,0 +1,252 @@
+use chrono::prelude::*;
+use regex::{Captures, Regex};
+
+use crate::message::{MessageError, MessageErrorKind, Result};
+
+// chunk of a line that looks like a timestamp in any of the known dialects
+pub const DATETIME: &str = r"\d{1,4}[./-]\d{1,2}[./-]\d{1,4}\.?,?\s\d{1,2}[:.]\d{2}(?:[:.]\d{2})?(?:[\s\x{202f}\x{a0}]?[AaPp]\.?\s?[Mm]\.?)?";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dialect {
+    // 2019-09-11, 01:57:17
+    YearMonthDay,
+    // 11/09/2019, 01:57 or 11.09.19, 01:57:17
+    DayMonthYear,
+    // 9/11/19, 1:57 PM
+    MonthDayYear,
+    // any `strftime` pattern, e.g. `%d-%m-%Y %H:%M`
+    Custom(String),
+}
+
+impl Dialect {
+    pub fn builtin() -> [Dialect; 3] {
+        [
+            Dialect::YearMonthDay,
+            Dialect::DayMonthYear,
+            Dialect::MonthDayYear,
+        ]
+    }
+
+    // a custom pattern has to have a `%` specifier, anything else is taken for a misspelled name
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match name {
+            "ymd" => Some(Dialect::YearMonthDay),
+            "dmy" => Some(Dialect::DayMonthYear),
+            "mdy" => Some(Dialect::MonthDayYear),
+            pattern if pattern.contains('%') => Some(Dialect::Custom(pattern.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn parse(&self, raw: &str) -> Result<NaiveDateTime> {
+        lazy_static! {
+            static ref PARTS: Regex = Regex::new(r"^(?P<a>\d{1,4})[./-](?P<b>\d{1,2})[./-](?P<c>\d{1,4})\.?,?\s+(?P<hour>\d{1,2})[:.](?P<minute>\d{2})(?:[:.](?P<second>\d{2}))?(?:\s*(?P<meridiem>[AaPp])\.?\s?[Mm]\.?)?$").expect("invalid regex");
+        }
+
+        let raw = normalize(raw);
+
+        if let Dialect::Custom(pattern) = self {
+            return NaiveDateTime::parse_from_str(&raw, pattern)
+                .map_err(|_| MessageError(MessageErrorKind::InvalidDate));
+        }
+
+        PARTS
+            .captures(&raw)
+            .and_then(|parts| self.read_parts(&parts))
+            .ok_or(MessageError(MessageErrorKind::InvalidDate))
+    }
+
+    fn read_parts(&self, parts: &Captures) -> Option<NaiveDateTime> {
+        let (year, month, day) = match self {
+            Dialect::YearMonthDay if parts["a"].len() == 4 => {
+                (&parts["a"], &parts["b"], &parts["c"])
+            }
+            Dialect::DayMonthYear if parts["a"].len() <= 2 => {
+                (&parts["c"], &parts["b"], &parts["a"])
+            }
+            Dialect::MonthDayYear if parts["a"].len() <= 2 => {
+                (&parts["c"], &parts["a"], &parts["b"])
+            }
+            _ => return None,
+        };
+
+        let year = match year.len() {
+            2 => 2000 + year.parse::<i32>().ok()?,
+            4 => year.parse::<i32>().ok()?,
+            _ => return None,
+        };
+
+        let mut hour: u32 = parts["hour"].parse().ok()?;
+        if let Some(meridiem) = parts.name("meridiem") {
+            if hour == 0 || hour > 12 {
+                return None;
+            }
+
+            hour %= 12;
+            if meridiem.as_str().eq_ignore_ascii_case("p") {
+                hour += 12;
+            }
+        }
+
+        let minute = parts["minute"].parse().ok()?;
+        let second = match parts.name("second") {
+            Some(v) => v.as_str().parse().ok()?,
+            None => 0,
+        };
+
+        NaiveDate::from_ymd_opt(year, month.parse().ok()?, day.parse().ok()?)?
+            .and_hms_opt(hour, minute, second)
+    }
+
+    // picks the only builtin dialect that reads every timestamp as a valid date and keeps them in
+    // chronological order, readings that end up with identical dates are not considered ambiguous
+    pub fn detect(raw: &[&str]) -> Result<Dialect> {
+        let mut candidates: Vec<(Dialect, Vec<NaiveDateTime>)> = Vec::new();
+
+        for dialect in Dialect::builtin().iter() {
+            let parsed: Result<Vec<NaiveDateTime>> = raw.iter().map(|v| dialect.parse(v)).collect();
+
+            if let Ok(parsed) = parsed {
+                if parsed.windows(2).all(|w| w[0] <= w[1]) {
+                    candidates.push((dialect.clone(), parsed));
+                }
+            }
+        }
+
+        let (dialect, first) = match candidates.first() {
+            Some(v) => v,
+            None => return Err(MessageError(MessageErrorKind::InvalidDate)),
+        };
+
+        if candidates.iter().any(|(_, parsed)| parsed != first) {
+            return Err(MessageError(MessageErrorKind::AmbiguousDate));
+        }
+
+        Ok(dialect.clone())
+    }
+}
+
+// exports put a narrow no-break space between time and meridiem on some devices
+fn normalize(raw: &str) -> String {
+    raw.trim().replace(['\u{202f}', '\u{a0}'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! assert_parses {
+        ($dialect: expr, $raw: expr, $expected: expr) => {
+            assert_eq!(
+                format!("{}", $dialect.parse($raw).unwrap()),
+                $expected,
+                "with: {}",
+                $raw
+            );
+        };
+    }
+
+    #[test]
+    fn parse_works_for_builtin_dialects() {
+        assert_parses!(
+            Dialect::YearMonthDay,
+            "2019-09-11, 01:57:17",
+            "2019-09-11 01:57:17"
+        );
+        assert_parses!(
+            Dialect::DayMonthYear,
+            "11/09/2019, 01:57",
+            "2019-09-11 01:57:00"
+        );
+        assert_parses!(
+            Dialect::DayMonthYear,
+            "11.09.19, 01:57:17",
+            "2019-09-11 01:57:17"
+        );
+        assert_parses!(
+            Dialect::MonthDayYear,
+            "9/11/19, 1:57 PM",
+            "2019-09-11 13:57:00"
+        );
+        assert_parses!(
+            Dialect::MonthDayYear,
+            "9/11/19, 12:57:17\u{202f}AM",
+            "2019-09-11 00:57:17"
+        );
+        assert_parses!(
+            Dialect::DayMonthYear,
+            "11/9/19, 12:07 p. m.",
+            "2019-09-11 12:07:00"
+        );
+    }
+
+    #[test]
+    fn parse_works_for_custom_dialect() {
+        assert_parses!(
+            Dialect::from_name("%d-%m-%Y %H:%M").unwrap(),
+            "11-09-2019 01:57",
+            "2019-09-11 01:57:00"
+        );
+    }
+
+    #[test]
+    fn from_name_works() {
+        assert_eq!(Dialect::from_name("dmy"), Some(Dialect::DayMonthYear));
+        assert_eq!(
+            Dialect::from_name("%d-%m-%Y %H:%M"),
+            Some(Dialect::Custom(String::from("%d-%m-%Y %H:%M")))
+        );
+        assert_eq!(Dialect::from_name("dym"), None);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_dates() {
+        assert!(Dialect::DayMonthYear.parse("31/02/2019, 01:57").is_err());
+        assert!(Dialect::MonthDayYear.parse("9/11/19, 13:57 PM").is_err());
+        assert!(Dialect::YearMonthDay.parse("11.09.19, 01:57:17").is_err());
+    }
+
+    #[test]
+    fn detect_works() {
+        assert_eq!(
+            Dialect::detect(&["12/09/2019, 01:57", "13/09/2019, 01:57"]).unwrap(),
+            Dialect::DayMonthYear
+        );
+        assert_eq!(
+            Dialect::detect(&["9/12/19, 1:57 AM", "9/13/19, 1:57 AM"]).unwrap(),
+            Dialect::MonthDayYear
+        );
+        assert_eq!(
+            Dialect::detect(&["2019-09-11, 01:57:17"]).unwrap(),
+            Dialect::YearMonthDay
+        );
+    }
+
+    #[test]
+    fn detect_uses_chronological_order() {
+        // both readings are valid dates, only day/month keeps them in order
+        assert_eq!(
+            Dialect::detect(&[
+                "01/02/2019, 10:00",
+                "03/02/2019, 10:00",
+                "01/03/2019, 10:00"
+            ])
+            .unwrap(),
+            Dialect::DayMonthYear
+        );
+    }
+
+    #[test]
+    fn detect_reports_ambiguity() {
+        let err = Dialect::detect(&["01/02/2019, 10:00", "03/04/2019, 10:00"]).unwrap_err();
+        assert!(matches!(err.0, MessageErrorKind::AmbiguousDate));
+    }
+
+    #[test]
+    fn detect_ignores_identical_readings() {
+        assert_eq!(
+            Dialect::detect(&["01/01/2019, 10:00", "02/02/2019, 10:00"]).unwrap(),
+            Dialect::DayMonthYear
+        );
+    }
+}
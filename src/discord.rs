@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use serde::de::{Error, IgnoredAny};
+use serde_json::{Result, Value};
+
+use crate::attachment::Attachment;
+use crate::conversation::Conversation;
+use crate::event::ChatEvent;
+use crate::message::{normalize, Message, MessageType};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Export {
+    messages: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    name: String,
+    nickname: Option<String>,
+}
+
+impl Author {
+    // the nickname is what the server shows, falls back to the account name
+    fn display_name(&self) -> String {
+        normalize(
+            self.nickname
+                .as_deref()
+                .filter(|v| !v.is_empty())
+                .unwrap_or(&self.name),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct File {
+    file_name: String,
+    file_size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Reference {
+    message_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: DateTime<FixedOffset>,
+    timestamp_edited: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    content: String,
+    author: Author,
+    #[serde(default)]
+    attachments: Vec<File>,
+    #[serde(default)]
+    stickers: Vec<IgnoredAny>,
+    #[serde(default)]
+    mentions: Vec<Author>,
+    reference: Option<Reference>,
+}
+
+impl Entry {
+    fn event(&self) -> Option<ChatEvent> {
+        let by = self.author.display_name();
+        let mentions: Vec<String> = self.mentions.iter().map(|m| m.display_name()).collect();
+
+        match self.kind.as_str() {
+            "RecipientAdd" => Some(ChatEvent::MemberAdded {
+                by,
+                members: mentions,
+            }),
+            "RecipientRemove" => match mentions.into_iter().next() {
+                Some(member) if member != by => Some(ChatEvent::MemberRemoved { by, member }),
+                _ => Some(ChatEvent::MemberLeft { member: by }),
+            },
+            "GuildMemberJoin" => Some(ChatEvent::MemberJoined { member: by }),
+            "ChannelNameChange" => Some(ChatEvent::SubjectChanged {
+                by,
+                subject: self.content.clone(),
+            }),
+            "ChannelIconChange" => Some(ChatEvent::IconChanged { by }),
+            _ => None,
+        }
+    }
+
+    // everything but plain messages and replies is a notice from discord itself, calls aside
+    fn is_system(&self) -> bool {
+        !matches!(self.kind.as_str(), "Default" | "Reply" | "Call")
+    }
+
+    fn message_kind(&self, attachment: &Option<Attachment>) -> MessageType {
+        if self.kind == "Call" {
+            return MessageType::Call {
+                video: false,
+                missed: false,
+            };
+        }
+
+        if !self.stickers.is_empty() {
+            return MessageType::Sticker;
+        }
+
+        match attachment {
+            Some(attachment) => attachment.kind(),
+            None => MessageType::Text,
+        }
+    }
+}
+
+pub fn parse(raw: &str) -> Result<Conversation> {
+    let export: Value = serde_json::from_str(raw)?;
+
+    // DiscordChatExporter writes a channel along with the guild it belongs to, that's what tells
+    // it apart from other exports with a list of `messages` like telegram's `result.json`
+    if export.get("guild").is_none() {
+        return Err(serde_json::Error::missing_field("guild"));
+    }
+
+    let export: Export = serde_json::from_value(export)?;
+
+    // replies point at the id of the message they answer
+    let ids: HashMap<&str, DateTime<FixedOffset>> = export
+        .messages
+        .iter()
+        .map(|e| (e.id.as_str(), e.timestamp))
+        .collect();
+
+    let mut messages = Vec::new();
+    let mut events = Vec::new();
+
+    for entry in export.messages.iter() {
+        let datetime = entry.timestamp;
+        let author = entry.author.display_name();
+
+        if entry.is_system() {
+            events.push(Message {
+                datetime,
+                author,
+                text: normalize(&entry.content),
+                kind: MessageType::System,
+                event: entry.event(),
+                attachment: None,
+                edited: false,
+                reply_to: None,
+            });
+            continue;
+        }
+
+        let attachment = entry.attachments.first().map(|f| Attachment {
+            name: f.file_name.clone(),
+            size: f.file_size_bytes,
+        });
+
+        let reply_to = entry
+            .reference
+            .as_ref()
+            .and_then(|r| r.message_id.as_deref())
+            .and_then(|id| ids.get(id))
+            .cloned();
+
+        messages.push(Message {
+            datetime,
+            author,
+            text: normalize(&entry.content),
+            kind: entry.message_kind(&attachment),
+            event: None,
+            attachment,
+            edited: entry.timestamp_edited.is_some(),
+            reply_to,
+        });
+    }
+
+    Ok(Conversation::from_messages(messages, events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MOCK: &str = r#"{
+  "guild": {"id": "1", "name": "Foo's server", "iconUrl": ""},
+  "channel": {"id": "2", "type": "GuildTextChat", "category": "Text", "name": "general", "topic": null},
+  "dateRange": {"after": null, "before": null},
+  "messages": [
+    {
+      "id": "10",
+      "type": "GuildMemberJoin",
+      "timestamp": "2019-09-11T01:57:17.123+00:00",
+      "timestampEdited": null,
+      "isPinned": false,
+      "content": "",
+      "author": {"id": "100", "name": "bar", "discriminator": "0001", "nickname": "Bar", "isBot": false},
+      "attachments": [],
+      "embeds": [],
+      "stickers": [],
+      "reactions": [],
+      "mentions": []
+    },
+    {
+      "id": "11",
+      "type": "Default",
+      "timestamp": "2019-09-11T01:58:00+00:00",
+      "timestampEdited": "2019-09-11T01:59:00+00:00",
+      "isPinned": false,
+      "content": "welcome @Bar",
+      "author": {"id": "101", "name": "foo", "discriminator": "0002", "nickname": null, "isBot": false},
+      "attachments": [],
+      "embeds": [],
+      "stickers": [],
+      "reactions": [],
+      "mentions": [{"id": "100", "name": "bar", "discriminator": "0001", "nickname": "Bar", "isBot": false}]
+    },
+    {
+      "id": "12",
+      "type": "Reply",
+      "timestamp": "2019-09-11T02:00:00+00:00",
+      "timestampEdited": null,
+      "isPinned": false,
+      "content": "thanks, here is my cat",
+      "author": {"id": "100", "name": "bar", "discriminator": "0001", "nickname": "Bar", "isBot": false},
+      "attachments": [{"id": "200", "url": "cat.png", "fileName": "cat.png", "fileSizeBytes": 42}],
+      "embeds": [],
+      "stickers": [],
+      "reactions": [],
+      "mentions": [],
+      "reference": {"messageId": "11", "channelId": "2", "guildId": "1"}
+    }
+  ],
+  "messageCount": 3
+}"#;
+
+    #[test]
+    fn parse_works() {
+        let c = parse(MOCK).unwrap();
+
+        assert_eq!(c.count(), 2);
+        assert_eq!(c.participants(), &vec!["foo", "Bar"]);
+        assert!(c.first().unwrap().edited);
+        assert_eq!(
+            c.events()[0].event,
+            Some(ChatEvent::MemberJoined {
+                member: String::from("Bar"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_maps_replies_and_attachments() {
+        let c = parse(MOCK).unwrap();
+        let reply = c.last().unwrap();
+
+        assert_eq!(reply.reply_to, Some(c.first().unwrap().datetime));
+        assert_eq!(reply.kind, MessageType::Image);
+        assert_eq!(
+            reply.attachment,
+            Some(Attachment {
+                name: String::from("cat.png"),
+                size: Some(42),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_rejects_telegram_exports() {
+        let raw = r#"{"name": "Foo", "messages": [{"id": 1, "type": "message", "date": "2019-09-11T01:57:17", "text": ""}]}"#;
+        assert!(parse(raw).is_err());
+    }
+}
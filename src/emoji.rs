@@ -1,6 +1,9 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
 
-pub const EMOJI: [&'static str; 2414] = [
+use serde::Serializer;
+
+pub static EMOJI: [&str; 2414] = [
     "😀",
     "😁",
     "😂",
@@ -2419,7 +2422,19 @@ pub const EMOJI: [&'static str; 2414] = [
 
 pub type Emojis = HashMap<String, usize>;
 
-pub fn count(src: &String) -> Emojis {
+// the most used emojis first, ties in the order of their code points
+pub fn ranked(emojis: &Emojis) -> Vec<(&String, &usize)> {
+    let mut ranked: Vec<(&String, &usize)> = emojis.iter().collect();
+    ranked.sort_by_key(|(emoji, count)| (Reverse(**count), *emoji));
+
+    ranked
+}
+
+pub fn by_rank<S: Serializer>(emojis: &Emojis, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(ranked(emojis))
+}
+
+pub fn count(src: &str) -> Emojis {
     let mut map = HashMap::new();
 
     for emoji in EMOJI.iter() {
@@ -2441,14 +2456,22 @@ mod tests {
 
         for (k, &v) in e.iter() {
             match k.as_str() {
-                "🦀" => assert_eq!(v, usize::from(1 as u16)),
-                "😒" => assert_eq!(v, usize::from(1 as u16)),
-                "🔥" => assert_eq!(v, usize::from(1 as u16)),
-                "❌" => assert_eq!(v, usize::from(1 as u16)),
-                "💩" => assert_eq!(v, usize::from(2 as u16)),
-                "🙄" => assert_eq!(v, usize::from(1 as u16)),
+                "🦀" => assert_eq!(v, 1),
+                "😒" => assert_eq!(v, 1),
+                "🔥" => assert_eq!(v, 1),
+                "❌" => assert_eq!(v, 1),
+                "💩" => assert_eq!(v, 2),
+                "🙄" => assert_eq!(v, 1),
                 _ => panic!("should not fall here"),
             }
         }
     }
+
+    #[test]
+    fn ranked_works() {
+        let e = count(&String::from("🦀🔥💩🙄💩❌😒🦀"));
+        let ranked: Vec<&str> = ranked(&e).iter().map(|(k, _)| k.as_str()).collect();
+
+        assert_eq!(ranked, vec!["💩", "🦀", "❌", "🔥", "😒", "🙄"]);
+    }
 }
@@ -0,0 +1,259 @@
+use std::io::{self, BufRead, Read};
+
+// number of leading bytes looked at to tell utf-16 without a byte order mark from utf-8
+const SAMPLE: usize = 512;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    // the encoding of a text and the length of its byte order mark. without one, utf-16 is told
+    // apart by the zero bytes the ascii timestamps and punctuation leave on one side of each pair
+    pub fn detect(head: &[u8]) -> (Encoding, usize) {
+        if head.starts_with(b"\xef\xbb\xbf") {
+            return (Encoding::Utf8, 3);
+        }
+        if head.starts_with(b"\xff\xfe") {
+            return (Encoding::Utf16Le, 2);
+        }
+        if head.starts_with(b"\xfe\xff") {
+            return (Encoding::Utf16Be, 2);
+        }
+
+        let pairs = head[..head.len().min(SAMPLE)].chunks_exact(2);
+        let count = pairs.len();
+        let (even, odd) = pairs.fold((0, 0), |(even, odd), pair| {
+            (
+                even + (pair[0] == 0) as usize,
+                odd + (pair[1] == 0) as usize,
+            )
+        });
+
+        match (even, odd) {
+            (0, odd) if count > 0 && odd * 4 >= count => (Encoding::Utf16Le, 0),
+            (even, 0) if count > 0 && even * 4 >= count => (Encoding::Utf16Be, 0),
+            _ => (Encoding::Utf8, 0),
+        }
+    }
+
+    fn unit(&self, pair: [u8; 2]) -> u16 {
+        match self {
+            Encoding::Utf16Be => u16::from_be_bytes(pair),
+            _ => u16::from_le_bytes(pair),
+        }
+    }
+}
+
+// turns utf-16 into utf-8 as it's read, invalid surrogates end up as U+FFFD
+struct Utf16<R> {
+    reader: R,
+    encoding: Encoding,
+    // a byte or a high surrogate left over from the previous read
+    odd: Option<u8>,
+    high: Option<u16>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Utf16<R> {
+    fn new(reader: R, encoding: Encoding) -> Utf16<R> {
+        Utf16 {
+            reader,
+            encoding,
+            odd: None,
+            high: None,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+
+        while self.buf.is_empty() {
+            let chunk = self.reader.fill_buf()?;
+            if chunk.is_empty() {
+                if self.odd.take().is_some() || self.high.take().is_some() {
+                    self.buf.extend_from_slice("\u{fffd}".as_bytes());
+                }
+                return Ok(());
+            }
+
+            let mut bytes = Vec::with_capacity(chunk.len() + 1);
+            bytes.extend(self.odd.take());
+            bytes.extend_from_slice(chunk);
+            let len = chunk.len();
+            self.reader.consume(len);
+
+            if bytes.len() % 2 == 1 {
+                self.odd = bytes.pop();
+            }
+
+            let mut units: Vec<u16> = self.high.take().into_iter().collect();
+            units.extend(
+                bytes
+                    .chunks_exact(2)
+                    .map(|pair| self.encoding.unit([pair[0], pair[1]])),
+            );
+
+            // a surrogate pair can be split across reads
+            if units.last().is_some_and(|u| (0xd800..0xdc00).contains(u)) {
+                self.high = units.pop();
+            }
+
+            for c in char::decode_utf16(units) {
+                let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.buf
+                    .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Utf16<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = self.fill_buf()?.read(out)?;
+        self.consume(n);
+
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Utf16<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buf.len() {
+            self.fill()?;
+        }
+
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+// reads a text as utf-8 whatever it was written in, the byte order mark is dropped
+pub fn decode<R: BufRead + 'static>(mut reader: R) -> io::Result<Box<dyn BufRead>> {
+    let (encoding, bom) = Encoding::detect(reader.fill_buf()?);
+    reader.consume(bom);
+
+    match encoding {
+        Encoding::Utf8 => Ok(Box::new(reader)),
+        _ => Ok(Box::new(Utf16::new(reader, encoding))),
+    }
+}
+
+// same as `decode` for a text that's read as a whole, invalid utf-8 is refused
+pub fn decode_bytes(bytes: Vec<u8>) -> io::Result<String> {
+    let (encoding, bom) = Encoding::detect(&bytes);
+
+    match encoding {
+        Encoding::Utf8 => {
+            let mut bytes = bytes;
+            bytes.drain(..bom);
+            String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        }
+        _ => {
+            let units: Vec<u16> = bytes[bom..]
+                .chunks(2)
+                .map(|pair| encoding.unit([pair[0], pair.get(1).cloned().unwrap_or(0)]))
+                .collect();
+
+            Ok(String::from_utf16_lossy(&units))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CHAT: &str = "[2019-09-11, 01:57:17] Foo: Hey 💩\n[2019-09-11, 01:58:17] Bär: hi\n";
+
+    fn utf16(text: &str, bom: bool, be: bool) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let units = if bom { Some(0xfeff) } else { None }
+            .into_iter()
+            .chain(text.encode_utf16());
+
+        for unit in units {
+            if be {
+                bytes.extend_from_slice(&unit.to_be_bytes());
+            } else {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    fn read_all(bytes: Vec<u8>) -> String {
+        let mut text = String::new();
+        decode(io::Cursor::new(bytes))
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+
+        text
+    }
+
+    #[test]
+    fn detect_works() {
+        assert_eq!(Encoding::detect(b"\xef\xbb\xbfhey"), (Encoding::Utf8, 3));
+        assert_eq!(Encoding::detect(CHAT.as_bytes()), (Encoding::Utf8, 0));
+        assert_eq!(
+            Encoding::detect(&utf16(CHAT, true, false)),
+            (Encoding::Utf16Le, 2)
+        );
+        assert_eq!(
+            Encoding::detect(&utf16(CHAT, false, false)),
+            (Encoding::Utf16Le, 0)
+        );
+        assert_eq!(
+            Encoding::detect(&utf16(CHAT, false, true)),
+            (Encoding::Utf16Be, 0)
+        );
+        assert_eq!(Encoding::detect(b""), (Encoding::Utf8, 0));
+    }
+
+    #[test]
+    fn decode_works() {
+        assert_eq!(read_all(format!("\u{feff}{}", CHAT).into_bytes()), CHAT);
+        assert_eq!(read_all(utf16(CHAT, true, false)), CHAT);
+        assert_eq!(read_all(utf16(CHAT, false, false)), CHAT);
+        assert_eq!(read_all(utf16(CHAT, true, true)), CHAT);
+    }
+
+    #[test]
+    fn decode_handles_pairs_split_across_reads() {
+        // a tiny buffer splits the bytes of a unit and the units of a surrogate pair
+        for capacity in 1..8 {
+            let bytes = io::Cursor::new(utf16(CHAT, false, false));
+            let reader = io::BufReader::with_capacity(capacity, bytes);
+            let mut text = String::new();
+            Utf16::new(reader, Encoding::Utf16Le)
+                .read_to_string(&mut text)
+                .unwrap();
+
+            assert_eq!(text, CHAT, "{}", capacity);
+        }
+    }
+
+    #[test]
+    fn decode_bytes_works() {
+        assert_eq!(decode_bytes(utf16(CHAT, true, true)).unwrap(), CHAT);
+        assert_eq!(
+            decode_bytes(format!("\u{feff}{}", CHAT).into_bytes()).unwrap(),
+            CHAT
+        );
+        assert!(decode_bytes(vec![b'a', 0xff, b'b']).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use regex::Captures;
+
+use crate::locale::Locale;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChatEvent {
+    Encrypted,
+    Created { by: String, subject: String },
+    MemberAdded { by: String, members: Vec<String> },
+    MemberRemoved { by: String, member: String },
+    MemberJoined { member: String },
+    MemberLeft { member: String },
+    SubjectChanged { by: String, subject: String },
+    DescriptionChanged { by: String },
+    IconChanged { by: String },
+    NumberChanged { from: String, to: Option<String> },
+    AdminPromoted { member: String },
+}
+
+impl ChatEvent {
+    pub fn parse(text: &str, locale: &Locale) -> Option<ChatEvent> {
+        let text = text.trim_start_matches('\u{200e}').trim();
+
+        locale
+            .patterns()
+            .events
+            .iter()
+            .find_map(|(pattern, build)| pattern.captures(text).map(|c| build(&c, locale)))
+    }
+}
+
+// builders for the event patterns of a locale
+
+pub fn encrypted(_: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::Encrypted
+}
+
+pub fn created(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::Created {
+        by: c["by"].to_string(),
+        subject: c["subject"].to_string(),
+    }
+}
+
+pub fn added(c: &Captures, locale: &Locale) -> ChatEvent {
+    ChatEvent::MemberAdded {
+        by: c["by"].to_string(),
+        members: split_members(&c["members"], locale.and),
+    }
+}
+
+pub fn removed(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::MemberRemoved {
+        by: c["by"].to_string(),
+        member: c["member"].to_string(),
+    }
+}
+
+pub fn joined(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::MemberJoined {
+        member: c["member"].to_string(),
+    }
+}
+
+pub fn left(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::MemberLeft {
+        member: c["member"].to_string(),
+    }
+}
+
+pub fn subject(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::SubjectChanged {
+        by: c["by"].to_string(),
+        subject: c["subject"].to_string(),
+    }
+}
+
+pub fn description(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::DescriptionChanged {
+        by: c["by"].to_string(),
+    }
+}
+
+pub fn icon(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::IconChanged {
+        by: c["by"].to_string(),
+    }
+}
+
+pub fn number(c: &Captures, _: &Locale) -> ChatEvent {
+    ChatEvent::NumberChanged {
+        from: c["from"].to_string(),
+        to: c.name("to").map(|m| m.as_str().to_string()),
+    }
+}
+
+// `You're now an admin` comes without a name
+pub fn admin(c: &Captures, locale: &Locale) -> ChatEvent {
+    ChatEvent::AdminPromoted {
+        member: c
+            .name("member")
+            .map_or(locale.you, |m| m.as_str())
+            .to_string(),
+    }
+}
+
+// `Foo, Bar and Baz` -> [Foo, Bar, Baz]
+fn split_members(raw: &str, and: &str) -> Vec<String> {
+    let and = format!(" {} ", and);
+
+    raw.split(", ")
+        .flat_map(|v| v.split(and.as_str()))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_works_for_members() {
+        assert_eq!(
+            ChatEvent::parse("\u{200e}Foo added Bar, Baz and Qux", Locale::english()),
+            Some(ChatEvent::MemberAdded {
+                by: String::from("Foo"),
+                members: vec![
+                    String::from("Bar"),
+                    String::from("Baz"),
+                    String::from("Qux")
+                ],
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("Foo removed Bar", Locale::english()),
+            Some(ChatEvent::MemberRemoved {
+                by: String::from("Foo"),
+                member: String::from("Bar"),
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("Foo left", Locale::english()),
+            Some(ChatEvent::MemberLeft {
+                member: String::from("Foo"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_works_for_group_changes() {
+        assert_eq!(
+            ChatEvent::parse(
+                "Foo changed the subject from \"Old: one\" to \"New\"",
+                Locale::english()
+            ),
+            Some(ChatEvent::SubjectChanged {
+                by: String::from("Foo"),
+                subject: String::from("New"),
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse(
+                "\u{200e}Foo changed the subject to “New”",
+                Locale::english()
+            ),
+            Some(ChatEvent::SubjectChanged {
+                by: String::from("Foo"),
+                subject: String::from("New"),
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("Foo changed this group's icon", Locale::english()),
+            Some(ChatEvent::IconChanged {
+                by: String::from("Foo"),
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("Foo changed the group description", Locale::english()),
+            Some(ChatEvent::DescriptionChanged {
+                by: String::from("Foo"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_works_for_numbers_and_admins() {
+        assert_eq!(
+            ChatEvent::parse(
+                "+44 7700 900123 changed to +44 7700 900456",
+                Locale::english()
+            ),
+            Some(ChatEvent::NumberChanged {
+                from: String::from("+44 7700 900123"),
+                to: Some(String::from("+44 7700 900456")),
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("Foo changed their phone number to a new number. Tap to message or add the new number.", Locale::english()),
+            Some(ChatEvent::NumberChanged {
+                from: String::from("Foo"),
+                to: None,
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("You're now an admin", Locale::english()),
+            Some(ChatEvent::AdminPromoted {
+                member: String::from("You"),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_works_for_other_locales() {
+        let de = Locale::find("de").unwrap();
+        assert_eq!(
+            ChatEvent::parse("\u{200e}Foo hat Bar und Baz hinzugefügt", de),
+            Some(ChatEvent::MemberAdded {
+                by: String::from("Foo"),
+                members: vec![String::from("Bar"), String::from("Baz")],
+            })
+        );
+        assert_eq!(
+            ChatEvent::parse("Du bist jetzt ein Admin", de),
+            Some(ChatEvent::AdminPromoted {
+                member: String::from("Du"),
+            })
+        );
+
+        let es = Locale::find("es").unwrap();
+        assert_eq!(
+            ChatEvent::parse("Foo cambió el asunto a “Nuevo”", es),
+            Some(ChatEvent::SubjectChanged {
+                by: String::from("Foo"),
+                subject: String::from("Nuevo"),
+            })
+        );
+        assert_eq!(ChatEvent::parse("Foo left", es), None);
+    }
+
+    #[test]
+    fn parse_ignores_regular_text() {
+        assert_eq!(
+            ChatEvent::parse("Foo: I added salt", Locale::english()),
+            None
+        );
+        assert_eq!(ChatEvent::parse("see you later", Locale::english()), None);
+    }
+}
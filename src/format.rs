@@ -0,0 +1,111 @@
+use regex::Regex;
+
+use crate::dialect::DATETIME;
+
+// number of lines looked at while detecting the format of an export
+pub(crate) const SAMPLE_SIZE: usize = 100;
+
+// layout of a line in an exported chat, timestamps in either layout can be written in any of the
+// supported dialects
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // [2019-09-11, 01:57:17] Author: text
+    Ios,
+    // 9/11/19, 1:57 AM - Author: text
+    Android,
+}
+
+impl Format {
+    pub fn pattern(&self) -> &'static Regex {
+        lazy_static! {
+            static ref IOS: Regex = Regex::new(&format!(
+                r"^\x{{200e}}?\[(?P<datetime>{})\]\s(?P<author>.*?):\s(?P<text>.*)",
+                DATETIME
+            ))
+            .expect("invalid regex");
+            static ref ANDROID: Regex = Regex::new(&format!(
+                r"^(?P<datetime>{})\s-\s(?:(?P<author>.*?):\s)?(?P<text>.*)",
+                DATETIME
+            ))
+            .expect("invalid regex");
+        }
+
+        match self {
+            Format::Ios => &IOS,
+            Format::Android => &ANDROID,
+        }
+    }
+
+    // picks the format that matches most of the leading lines, falls back to ios
+    pub fn detect(raw: &str) -> Format {
+        let mut best = (Format::Ios, 0);
+
+        for format in [Format::Ios, Format::Android].iter() {
+            let hits = raw
+                .lines()
+                .take(SAMPLE_SIZE)
+                .filter(|line| format.pattern().is_match(line))
+                .count();
+
+            if hits > best.1 {
+                best = (*format, hits);
+            }
+        }
+
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_works_for_ios() {
+        let raw = "[2019-09-11, 01:57:17] Foo: Bar\n[2019-09-11, 01:58:17] Baz: Qux";
+        assert_eq!(Format::detect(raw), Format::Ios);
+    }
+
+    #[test]
+    fn detect_works_for_android() {
+        let raw = "12/31/19, 10:15 PM - Foo: Bar\n1/1/20, 12:01 AM - Baz: Qux";
+        assert_eq!(Format::detect(raw), Format::Android);
+    }
+
+    #[test]
+    fn detect_works_with_other_dialects() {
+        let raw = "[11.09.19, 01:57:17] Foo: Bar\n[11.09.19, 01:58:17] Baz: Qux";
+        assert_eq!(Format::detect(raw), Format::Ios);
+
+        let raw = "11/09/2019, 13:57 - Foo: Bar\n11/09/2019, 13:58 - Baz: Qux";
+        assert_eq!(Format::detect(raw), Format::Android);
+    }
+
+    #[test]
+    fn android_pattern_captures_system_lines() {
+        let c = Format::Android
+            .pattern()
+            .captures("12/31/19, 10:15 PM - Foo added Bar")
+            .unwrap();
+
+        assert!(c.name("author").is_none());
+        assert_eq!(&c["text"], "Foo added Bar");
+    }
+
+    #[test]
+    fn detect_falls_back_to_ios() {
+        assert_eq!(Format::detect("lorem ipsum"), Format::Ios);
+    }
+
+    #[test]
+    fn android_pattern_captures_parts() {
+        let c = Format::Android
+            .pattern()
+            .captures("12/31/19, 10:15 PM - Bar Baz: Hey: you")
+            .unwrap();
+
+        assert_eq!(&c["datetime"], "12/31/19, 10:15 PM");
+        assert_eq!(&c["author"], "Bar Baz");
+        assert_eq!(&c["text"], "Hey: you");
+    }
+}
@@ -0,0 +1,399 @@
+use std::fmt::Write;
+
+use crate::conversation::{Timeline, TimelineType};
+use crate::emoji;
+use crate::summary::Summary;
+
+// colours participants are drawn in, in the order of the leaderboard
+const PALETTE: [&str; 10] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+    "#9c755f", "#bab0ac",
+];
+// emojis on the leaderboard
+const TOP_EMOJIS: usize = 10;
+
+const WIDTH: usize = 800;
+const HEIGHT: usize = 240;
+// room left for the labels around a chart
+const MARGIN: usize = 30;
+
+const STYLE: &str = "
+body { font-family: -apple-system, 'Segoe UI', Helvetica, Arial, sans-serif; margin: 2em auto;
+       max-width: 860px; color: #222; }
+h1 { font-size: 1.6em; }
+h2 { font-size: 1.2em; margin-top: 2em; }
+table { border-collapse: collapse; }
+td { padding: 0.2em 1em 0.2em 0; }
+svg text { font-size: 11px; fill: #555; }
+.cards { display: flex; flex-wrap: wrap; gap: 1em; }
+.card { border: 1px solid #ddd; border-radius: 6px; padding: 0.8em 1em; min-width: 160px; }
+.card h3 { margin: 0 0 0.4em; font-size: 1em; }
+.swatch { display: inline-block; width: 0.8em; height: 0.8em; margin-right: 0.3em; }
+.legend span { margin-right: 1em; }
+";
+
+// text as it can be put in html, inside elements and attributes alike
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn color(i: usize) -> &'static str {
+    PALETTE[i % PALETTE.len()]
+}
+
+fn share(part: usize, whole: usize) -> f32 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f32 * 100.0 / whole as f32
+    }
+}
+
+fn summary_table(out: &mut String, summary: &Summary) {
+    let (words, letters) = summary.average();
+    let datetime = |m: Option<&crate::message::Message>| {
+        m.map(|m| m.datetime.format("%Y-%m-%d %H:%M %:z").to_string())
+            .unwrap_or_default()
+    };
+
+    let rows = [
+        ("First message", datetime(summary.first())),
+        ("Last message", datetime(summary.last())),
+        ("Messages", summary.count().to_string()),
+        ("Words", summary.words().to_string()),
+        ("Letters", summary.letters().to_string()),
+        ("Participants", summary.participants().len().to_string()),
+        ("Words per message", format!("{:.2}", words)),
+        ("Letters per message", format!("{:.2}", letters)),
+    ];
+
+    out.push_str("<table>\n");
+    for (label, value) in rows.iter() {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td><b>{}</b></td></tr>",
+            label,
+            escape(value)
+        )
+        .unwrap();
+    }
+    out.push_str("</table>\n");
+}
+
+fn legend(out: &mut String, names: &[String]) {
+    out.push_str("<p class=\"legend\">");
+    for (i, name) in names.iter().enumerate() {
+        write!(
+            out,
+            "<span><i class=\"swatch\" style=\"background:{}\"></i>{}</span>",
+            color(i),
+            escape(name)
+        )
+        .unwrap();
+    }
+    out.push_str("</p>\n");
+}
+
+// one bar per period, split by participant
+fn timeline_chart(out: &mut String, timeline: &Timeline, names: &[String]) {
+    let periods = timeline.messages_per_participant();
+    let max = periods
+        .iter()
+        .map(|(_, participants)| participants.values().sum::<usize>())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let plot = HEIGHT - MARGIN;
+    let step = (WIDTH - MARGIN) as f32 / periods.len().max(1) as f32;
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"100%\">",
+        WIDTH, HEIGHT
+    )
+    .unwrap();
+    writeln!(out, "<text x=\"0\" y=\"10\">{}</text>", max).unwrap();
+
+    for (i, (label, participants)) in periods.iter().enumerate() {
+        let x = MARGIN as f32 + step * i as f32;
+        let mut y = plot as f32;
+
+        for (j, name) in names.iter().enumerate() {
+            let count = participants.get(name).cloned().unwrap_or(0);
+            if count == 0 {
+                continue;
+            }
+
+            let height = count as f32 * plot as f32 / max as f32;
+            y -= height;
+            writeln!(
+                out,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}: {} {}</title></rect>",
+                x,
+                y,
+                (step * 0.9).max(0.5),
+                height,
+                color(j),
+                escape(label),
+                escape(name),
+                count
+            )
+            .unwrap();
+        }
+    }
+
+    if let (Some((first, _)), Some((last, _))) = (periods.first(), periods.last()) {
+        writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\">{}</text>",
+            MARGIN,
+            HEIGHT - 8,
+            escape(first)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>",
+            WIDTH,
+            HEIGHT - 8,
+            escape(last)
+        )
+        .unwrap();
+    }
+
+    out.push_str("</svg>\n");
+}
+
+fn hours_chart(out: &mut String, summary: &Summary) {
+    let frequency = summary.frequency();
+    let max = frequency.values().cloned().max().unwrap_or(0).max(1) as f32;
+    let plot = (HEIGHT - MARGIN) as f32;
+    let step = (WIDTH - MARGIN) as f32 / 24.0;
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"100%\">",
+        WIDTH, HEIGHT
+    )
+    .unwrap();
+
+    for (i, (hour, count)) in frequency.iter().enumerate() {
+        let x = MARGIN as f32 + step * i as f32;
+        let height = *count as f32 * plot / max;
+
+        writeln!(
+            out,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}: {}</title></rect>",
+            x,
+            plot - height,
+            step * 0.8,
+            height,
+            color(0),
+            hour,
+            count
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+            x + step * 0.4,
+            HEIGHT - 8,
+            &hour[..2]
+        )
+        .unwrap();
+    }
+
+    out.push_str("</svg>\n");
+}
+
+fn emoji_chart(out: &mut String, summary: &Summary) {
+    let emojis = summary.emojis();
+    let ranked: Vec<_> = emoji::ranked(&emojis)
+        .into_iter()
+        .take(TOP_EMOJIS)
+        .collect();
+
+    if ranked.is_empty() {
+        out.push_str("<p>No emojis were used.</p>\n");
+        return;
+    }
+
+    let max = *ranked[0].1 as f32;
+    let row = 24;
+    let width = (WIDTH - 2 * MARGIN - 60) as f32;
+
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" width=\"100%\">",
+        WIDTH,
+        row * ranked.len()
+    )
+    .unwrap();
+
+    for (i, (emoji, count)) in ranked.iter().enumerate() {
+        let y = row * i;
+        let length = **count as f32 * width / max;
+
+        writeln!(
+            out,
+            "<text x=\"0\" y=\"{}\" style=\"font-size:16px\">{}</text>",
+            y + 17,
+            escape(emoji)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "<rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"></rect>",
+            MARGIN,
+            y + 4,
+            length,
+            row - 8,
+            color(1)
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "<text x=\"{:.1}\" y=\"{}\">{}</text>",
+            MARGIN as f32 + length + 6.0,
+            y + 16,
+            count
+        )
+        .unwrap();
+    }
+
+    out.push_str("</svg>\n");
+}
+
+fn cards(out: &mut String, summary: &Summary) {
+    let frequency = summary.frequency_per_participant();
+
+    out.push_str("<div class=\"cards\">\n");
+    for (i, (name, stats)) in summary.leaderboard().iter().enumerate() {
+        // the earliest of the busiest hours
+        let busiest = frequency.get(name).and_then(|hours| {
+            hours
+                .iter()
+                .max_by_key(|(hour, count)| (*count, std::cmp::Reverse(*hour)))
+                .map(|(hour, _)| hour.clone())
+        });
+
+        writeln!(
+            out,
+            "<div class=\"card\"><h3><i class=\"swatch\" style=\"background:{}\"></i>{}</h3>\
+             {} messages ({:.1}%)<br>{} words<br>{} letters<br>busiest at {}</div>",
+            color(i),
+            escape(name),
+            stats.messages,
+            share(stats.messages, summary.count()),
+            stats.words,
+            stats.letters,
+            busiest.unwrap_or_default()
+        )
+        .unwrap();
+    }
+    out.push_str("</div>\n");
+}
+
+// a page that needs nothing but itself, styles and charts are inline
+pub fn render(summary: &Summary, timeline_type: TimelineType) -> String {
+    let names: Vec<String> = summary
+        .leaderboard()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    let mut out = String::new();
+
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    out.push_str("<title>Chat insights</title>\n");
+    writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE).unwrap();
+    out.push_str("<h1>Chat insights</h1>\n");
+    summary_table(&mut out, summary);
+
+    writeln!(out, "<h2>Timeline ({})</h2>", timeline_type).unwrap();
+    legend(&mut out, &names);
+    timeline_chart(&mut out, &summary.timeline(timeline_type), &names);
+
+    out.push_str("<h2>Hours of the day</h2>\n");
+    hours_chart(&mut out, summary);
+
+    out.push_str("<h2>Emojis</h2>\n");
+    emoji_chart(&mut out, summary);
+
+    out.push_str("<h2>Participants</h2>\n");
+    cards(&mut out, summary);
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversation::Conversation;
+
+    static MOCK: &str = r"
+[2019-09-11, 01:57:17] Foo: Hey! 💩
+[2019-09-11, 13:01:00] <Bar> & Baz: heyyyyyyy, 'sup 💩💩
+[2019-09-13, 22:15:00] Foo: there
+[2019-10-01, 00:00:00] <Bar> & Baz: 🙄
+[2019-12-24, 09:30:00] Foo: bye
+";
+
+    fn render_mock() -> String {
+        let c = Conversation::from_str(MOCK).unwrap();
+        render(&Summary::from(&c), TimelineType::Monthly)
+    }
+
+    #[test]
+    fn escape_works() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn render_works() {
+        let page = render_mock();
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert_eq!(page.matches("<svg").count(), 3);
+        // 24 hours, 2 emojis and 4 months, one with both participants and one with nobody
+        assert_eq!(page.matches("<rect").count(), 24 + 2 + 4);
+        assert!(page.contains("<title>2019-09-01T00:00:00: Foo 2</title>"));
+        assert!(page.contains("<title>01h: 1</title>"));
+        assert!(page.contains("&lt;Bar&gt; &amp; Baz</h3>2 messages (40.0%)"));
+        assert!(
+            page.contains("Foo</h3>3 messages (60.0%)<br>3 words<br>12 letters<br>busiest at 01h")
+        );
+        assert!(!page.contains("<Bar>"));
+    }
+
+    #[test]
+    fn render_is_self_contained() {
+        let page = render_mock();
+
+        assert!(!page.contains("<script"));
+        assert!(!page.contains("<link"));
+        assert!(!page.contains("src="));
+        assert_eq!(
+            page.matches("://").count(),
+            page.matches("http://www.w3.org/2000/svg").count()
+        );
+    }
+}
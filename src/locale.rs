@@ -0,0 +1,519 @@
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+
+use crate::attachment::Attachment;
+use crate::event::{self, ChatEvent};
+use crate::message::MessageType;
+
+// number of messages looked at while detecting the language of an export, as many as are read
+// ahead of a stream to detect its dialect at first
+pub(crate) const SAMPLE_SIZE: usize = 5000;
+
+pub type Builder = fn(&Captures, &Locale) -> ChatEvent;
+
+// markers WhatsApp writes in place of media and system lines, in the language the exporting phone
+// was set to. `pages`, `location` and the event patterns are regular expressions, `NAME` in an
+// event pattern stands for a participant
+#[derive(Debug)]
+pub struct Locale {
+    pub code: &'static str,
+    pub you: &'static str,
+    pub and: &'static str,
+    pub attached: &'static str,
+    pub file_attached: &'static str,
+    pub image: &'static str,
+    pub audio: &'static str,
+    pub video: &'static str,
+    pub contact: &'static str,
+    pub sticker: &'static str,
+    pub gif: &'static str,
+    pub media: &'static str,
+    pub edited: &'static str,
+    pub document: &'static str,
+    pub pages: &'static str,
+    pub deleted: &'static [&'static str],
+    pub view_once: &'static [&'static str],
+    pub live_location: &'static [&'static str],
+    pub location: &'static str,
+    pub poll: &'static str,
+    pub missed_voice_call: &'static str,
+    pub missed_video_call: &'static str,
+    pub voice_call: &'static str,
+    pub video_call: &'static str,
+    pub events: &'static [(&'static str, Builder)],
+}
+
+pub struct Patterns {
+    pub attached: Regex,
+    pub file_attached: Regex,
+    pub document: Regex,
+    pub location: Regex,
+    pub edited: Regex,
+    pub events: Vec<(Regex, Builder)>,
+}
+
+pub static LOCALES: [Locale; 5] = [
+    Locale {
+        code: "en",
+        you: "You",
+        and: "and",
+        attached: "attached",
+        file_attached: "file attached",
+        image: "image omitted",
+        audio: "audio omitted",
+        video: "video omitted",
+        contact: "Contact card omitted",
+        sticker: "sticker omitted",
+        gif: "GIF omitted",
+        media: "<Media omitted>",
+        edited: "<This message was edited>",
+        document: "document omitted",
+        pages: "pages?",
+        deleted: &["This message was deleted", "You deleted this message"],
+        view_once: &[
+            "view once image omitted",
+            "view once video omitted",
+            "view once voice message omitted",
+        ],
+        live_location: &["Live location shared", "live location shared"],
+        location: "Location",
+        poll: "POLL:",
+        missed_voice_call: "Missed voice call",
+        missed_video_call: "Missed video call",
+        voice_call: "Voice call",
+        video_call: "Video call",
+        events: &[
+            (
+                r"(?:Messages to this group are now secured with end-to-end encryption|Messages and calls are end-to-end encrypted)\..*",
+                event::encrypted,
+            ),
+            (
+                r#"(?P<by>NAME) created group ["“](?P<subject>.*)["”]"#,
+                event::created,
+            ),
+            (r"(?P<by>NAME) added (?P<members>.+)", event::added),
+            (r"(?P<by>NAME) removed (?P<member>.+)", event::removed),
+            (
+                r"(?P<member>NAME) joined using this group's invite link",
+                event::joined,
+            ),
+            (r"(?P<member>NAME) left", event::left),
+            (
+                r#"(?P<by>NAME) changed the subject (?:from ["“].*["”] )?to ["“](?P<subject>.*)["”]"#,
+                event::subject,
+            ),
+            (
+                r"(?P<by>NAME) changed the group description",
+                event::description,
+            ),
+            (r"(?P<by>NAME) changed this group's icon", event::icon),
+            (
+                r"(?P<from>NAME) changed their phone number to (?:a new number\..*|(?P<to>.+))",
+                event::number,
+            ),
+            (
+                r"(?P<from>NAME) changed to (?P<to>\+[\d\s\-()]+)",
+                event::number,
+            ),
+            (r"You're now an admin", event::admin),
+            (r"(?P<member>NAME) (?:is|are) now an admin", event::admin),
+        ],
+    },
+    Locale {
+        code: "de",
+        you: "Du",
+        and: "und",
+        attached: "Anhang",
+        file_attached: "Datei angehängt",
+        image: "Bild weggelassen",
+        audio: "Audio weggelassen",
+        video: "Video weggelassen",
+        contact: "Kontaktkarte weggelassen",
+        sticker: "Sticker weggelassen",
+        gif: "GIF weggelassen",
+        media: "<Medien ausgeschlossen>",
+        edited: "<Diese Nachricht wurde bearbeitet>",
+        document: "Dokument weggelassen",
+        pages: "Seiten?",
+        deleted: &[
+            "Diese Nachricht wurde gelöscht.",
+            "Du hast diese Nachricht gelöscht.",
+        ],
+        view_once: &[],
+        live_location: &["Live-Standort wird geteilt"],
+        location: "Standort",
+        poll: "UMFRAGE:",
+        missed_voice_call: "Verpasster Sprachanruf",
+        missed_video_call: "Verpasster Videoanruf",
+        voice_call: "Sprachanruf",
+        video_call: "Videoanruf",
+        events: &[
+            (
+                r"(?:Nachrichten und Anrufe sind Ende-zu-Ende-verschlüsselt|Nachrichten in dieser Gruppe sind jetzt mit Ende-zu-Ende-Verschlüsselung geschützt)\..*",
+                event::encrypted,
+            ),
+            (
+                r#"(?P<by>NAME) hat die Gruppe ["„“](?P<subject>.*)["“”] erstellt"#,
+                event::created,
+            ),
+            (
+                r"(?P<by>NAME) hat (?P<members>.+) hinzugefügt",
+                event::added,
+            ),
+            (r"(?P<by>NAME) hat (?P<member>.+) entfernt", event::removed),
+            (
+                r"(?P<member>NAME) ist der Gruppe über den Einladungslink beigetreten",
+                event::joined,
+            ),
+            (r"(?P<member>NAME) hat die Gruppe verlassen", event::left),
+            (
+                r#"(?P<by>NAME) hat den Betreff (?:von ["„“].*["“”] )?zu ["„“](?P<subject>.*)["“”] geändert"#,
+                event::subject,
+            ),
+            (
+                r"(?P<by>NAME) hat die Gruppenbeschreibung geändert",
+                event::description,
+            ),
+            (r"(?P<by>NAME) hat das Gruppenbild geändert", event::icon),
+            (
+                r"(?P<from>NAME) hat (?:seine|ihre|die) Telefonnummer (?:zu einer neuen Nummer|zu (?P<to>.+)) geändert.*",
+                event::number,
+            ),
+            (
+                r"(?P<from>NAME) hat zu (?P<to>\+[\d\s\-()]+) gewechselt",
+                event::number,
+            ),
+            (r"Du bist jetzt ein Admin", event::admin),
+            (r"(?P<member>NAME) ist jetzt ein Admin", event::admin),
+        ],
+    },
+    Locale {
+        code: "es",
+        you: "Tú",
+        and: "y",
+        attached: "adjunto",
+        file_attached: "archivo adjunto",
+        image: "imagen omitida",
+        audio: "audio omitido",
+        video: "video omitido",
+        contact: "tarjeta de contacto omitida",
+        sticker: "sticker omitido",
+        gif: "GIF omitido",
+        media: "<Multimedia omitido>",
+        edited: "<Se editó este mensaje.>",
+        document: "documento omitido",
+        pages: "páginas?",
+        deleted: &["Se eliminó este mensaje.", "Eliminaste este mensaje."],
+        view_once: &[],
+        live_location: &["Ubicación en tiempo real compartida"],
+        location: "Ubicación",
+        poll: "ENCUESTA:",
+        missed_voice_call: "Llamada perdida",
+        missed_video_call: "Videollamada perdida",
+        voice_call: "Llamada de voz",
+        video_call: "Videollamada",
+        events: &[
+            (
+                r"(?:Los mensajes y las llamadas están cifrados de extremo a extremo|Los mensajes en este grupo ahora están protegidos con cifrado de extremo a extremo)\..*",
+                event::encrypted,
+            ),
+            (
+                r#"(?P<by>NAME) creó el grupo ["“](?P<subject>.*)["”]"#,
+                event::created,
+            ),
+            (r"(?P<by>NAME) añadió a (?P<members>.+)", event::added),
+            (r"(?P<by>NAME) eliminó a (?P<member>.+)", event::removed),
+            (
+                r"(?P<member>NAME) se unió usando el enlace de invitación de este grupo",
+                event::joined,
+            ),
+            (r"(?P<member>NAME) salió del grupo", event::left),
+            (
+                r#"(?P<by>NAME) cambió el asunto (?:de ["“].*["”] )?a ["“](?P<subject>.*)["”]"#,
+                event::subject,
+            ),
+            (
+                r"(?P<by>NAME) cambió la descripción del grupo",
+                event::description,
+            ),
+            (r"(?P<by>NAME) cambió el ícono de este grupo", event::icon),
+            (
+                r"(?P<from>NAME) cambió su número de teléfono a (?:un número nuevo\..*|(?P<to>.+))",
+                event::number,
+            ),
+            (
+                r"(?P<from>NAME) cambió a (?P<to>\+[\d\s\-()]+)",
+                event::number,
+            ),
+            (r"Ahora eres admin\. del grupo", event::admin),
+            (r"(?P<member>NAME) ahora es admin\. del grupo", event::admin),
+        ],
+    },
+    Locale {
+        code: "ur",
+        you: "آپ",
+        and: "اور",
+        attached: "منسلک",
+        file_attached: "فائل منسلک ہے",
+        image: "تصویر خارج کر دی گئی",
+        audio: "آڈیو خارج کر دیا گیا",
+        video: "ویڈیو خارج کر دی گئی",
+        contact: "رابطہ کارڈ خارج کر دیا گیا",
+        sticker: "اسٹیکر خارج کر دیا گیا",
+        gif: "GIF خارج کر دیا گیا",
+        media: "<میڈیا خارج کر دیا گیا>",
+        edited: "<یہ پیغام ترمیم کیا گیا تھا>",
+        document: "دستاویز خارج کر دی گئی",
+        pages: "صفحات",
+        deleted: &["یہ پیغام حذف کر دیا گیا", "آپ نے یہ پیغام حذف کر دیا"],
+        view_once: &[],
+        live_location: &["لائیو مقام کا اشتراک کیا گیا"],
+        location: "مقام",
+        poll: "رائے شماری:",
+        missed_voice_call: "چھوٹی ہوئی صوتی کال",
+        missed_video_call: "چھوٹی ہوئی ویڈیو کال",
+        voice_call: "صوتی کال",
+        video_call: "ویڈیو کال",
+        events: &[
+            (
+                r"پیغامات اور کالز اینڈ ٹو اینڈ انکرپٹڈ ہیں.*",
+                event::encrypted,
+            ),
+            (
+                r#"(?P<by>NAME) نے گروپ ["“](?P<subject>.*)["”] بنایا"#,
+                event::created,
+            ),
+            (r"(?P<by>NAME) نے (?P<members>.+) کو شامل کیا", event::added),
+            (r"(?P<by>NAME) نے (?P<member>.+) کو ہٹا دیا", event::removed),
+            (
+                r"(?P<member>NAME) اس گروپ کے دعوتی لنک کے ذریعے شامل ہوئے",
+                event::joined,
+            ),
+            (r"(?P<member>NAME) نے گروپ چھوڑ دیا", event::left),
+            (
+                r#"(?P<by>NAME) نے موضوع (?:["“].*["”] سے )?تبدیل کر کے ["“](?P<subject>.*)["”] کر دیا"#,
+                event::subject,
+            ),
+            (
+                r"(?P<by>NAME) نے گروپ کی تفصیل تبدیل کی",
+                event::description,
+            ),
+            (r"(?P<by>NAME) نے اس گروپ کا آئیکن تبدیل کیا", event::icon),
+            (
+                r"(?P<from>NAME) نے اپنا فون نمبر (?:ایک نئے نمبر|(?P<to>.+)) میں تبدیل کر دیا.*",
+                event::number,
+            ),
+            (
+                r"(?P<from>NAME) تبدیل ہو کر (?P<to>\+[\d\s\-()]+) ہو گیا",
+                event::number,
+            ),
+            (r"آپ اب ایڈمن ہیں", event::admin),
+            (r"(?P<member>NAME) اب ایڈمن ہیں", event::admin),
+        ],
+    },
+    Locale {
+        code: "pt",
+        you: "Você",
+        and: "e",
+        attached: "anexado",
+        file_attached: "arquivo anexado",
+        image: "imagem ocultada",
+        audio: "áudio ocultado",
+        video: "vídeo ocultado",
+        contact: "cartão de contato omitido",
+        sticker: "figurinha omitida",
+        gif: "GIF omitido",
+        media: "<Mídia oculta>",
+        edited: "<Mensagem editada>",
+        document: "documento omitido",
+        pages: "páginas?",
+        deleted: &["Mensagem apagada", "Você apagou esta mensagem"],
+        view_once: &[],
+        live_location: &["Localização em tempo real compartilhada"],
+        location: "Localização",
+        poll: "ENQUETE:",
+        missed_voice_call: "Chamada de voz perdida",
+        missed_video_call: "Chamada de vídeo perdida",
+        voice_call: "Chamada de voz",
+        video_call: "Chamada de vídeo",
+        events: &[
+            (
+                r"(?:As mensagens e as chamadas são protegidas com a criptografia de ponta a ponta|As mensagens deste grupo estão protegidas com a criptografia de ponta a ponta)\..*",
+                event::encrypted,
+            ),
+            (
+                r#"(?P<by>NAME) criou o grupo ["“](?P<subject>.*)["”]"#,
+                event::created,
+            ),
+            (r"(?P<by>NAME) adicionou (?P<members>.+)", event::added),
+            (r"(?P<by>NAME) removeu (?P<member>.+)", event::removed),
+            (
+                r"(?P<member>NAME) entrou usando o link de convite deste grupo",
+                event::joined,
+            ),
+            (r"(?P<member>NAME) saiu", event::left),
+            (
+                r#"(?P<by>NAME) mudou o assunto (?:de ["“].*["”] )?para ["“](?P<subject>.*)["”]"#,
+                event::subject,
+            ),
+            (
+                r"(?P<by>NAME) mudou a descrição do grupo",
+                event::description,
+            ),
+            (r"(?P<by>NAME) mudou a imagem deste grupo", event::icon),
+            (
+                r"(?P<from>NAME) mudou seu número de telefone para (?:um novo número\..*|(?P<to>.+))",
+                event::number,
+            ),
+            (
+                r"(?P<from>NAME) mudou para (?P<to>\+[\d\s\-()]+)",
+                event::number,
+            ),
+            (r"Agora você é admin", event::admin),
+            (r"(?P<member>NAME) agora é admin", event::admin),
+        ],
+    },
+];
+
+impl Locale {
+    pub fn english() -> &'static Locale {
+        &LOCALES[0]
+    }
+
+    pub fn find(code: &str) -> Option<&'static Locale> {
+        LOCALES.iter().find(|l| l.code == code)
+    }
+
+    pub fn patterns(&self) -> &'static Patterns {
+        lazy_static! {
+            static ref PATTERNS: HashMap<&'static str, Patterns> =
+                LOCALES.iter().map(|l| (l.code, l.compile())).collect();
+        }
+
+        &PATTERNS[self.code]
+    }
+
+    // names never contain `: `, that keeps regular messages like `Foo: I added salt` out
+    fn compile(&self) -> Patterns {
+        let compile = |pattern: String| Regex::new(&pattern).expect("invalid regex");
+
+        Patterns {
+            attached: compile(format!(
+                r"^\x{{200e}}?<{}: (?P<name>[^>]+)>$",
+                regex::escape(self.attached)
+            )),
+            file_attached: compile(format!(
+                r"^\x{{200e}}?(?P<name>\S+) \({}\)",
+                regex::escape(self.file_attached)
+            )),
+            document: compile(format!(
+                r"^(?P<name>.+?)(?: • \x{{200e}}?\d+ {})? \x{{200e}}?{}$",
+                self.pages,
+                regex::escape(self.document)
+            )),
+            location: compile(format!(
+                r"^(?i:{}): https://maps\.google\.com/\?q=\S+$",
+                self.location
+            )),
+            edited: compile(format!(r"\s*\x{{200e}}?{}$", regex::escape(self.edited))),
+            events: self
+                .events
+                .iter()
+                .map(|(pattern, build)| {
+                    (
+                        compile(format!("^{}$", pattern).replace("NAME", "[^:]+?")),
+                        *build,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    // events are only looked for in system lines, their patterns would take plenty of ordinary
+    // messages like `I left` for one
+    fn matches(&self, text: &str, system: bool) -> bool {
+        MessageType::detect(text, self) != MessageType::Text
+            || (system && ChatEvent::parse(text, self).is_some())
+            || Attachment::parse(text, self).is_some()
+    }
+
+    // picks the locale whose markers show up in most of the messages, english wins a tie. each
+    // text comes with whether it's shaped like a system line
+    pub fn detect(texts: &[(&str, bool)]) -> &'static Locale {
+        let mut best = (Locale::english(), 0);
+
+        for locale in LOCALES.iter() {
+            let hits = texts
+                .iter()
+                .take(SAMPLE_SIZE)
+                .filter(|(text, system)| locale.matches(text, *system))
+                .count();
+
+            if hits > best.1 {
+                best = (locale, hits);
+            }
+        }
+
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_works() {
+        assert_eq!(Locale::find("de").unwrap().code, "de");
+        assert!(Locale::find("xx").is_none());
+    }
+
+    #[test]
+    fn patterns_compile() {
+        for locale in LOCALES.iter() {
+            assert_eq!(locale.patterns().events.len(), locale.events.len());
+        }
+    }
+
+    #[test]
+    fn detect_works() {
+        let texts = [
+            ("Hallo zusammen", false),
+            ("\u{200e}Bild weggelassen", true),
+            ("\u{200e}Foo hat Bar hinzugefügt", true),
+            ("Bis morgen", false),
+        ];
+        assert_eq!(Locale::detect(&texts).code, "de");
+
+        let texts = [
+            ("\u{200e}imagen omitida", true),
+            ("\u{200e}Foo salió del grupo", true),
+        ];
+        assert_eq!(Locale::detect(&texts).code, "es");
+
+        let texts = [
+            ("\u{200e}imagem ocultada", true),
+            ("\u{200e}Foo saiu", true),
+        ];
+        assert_eq!(Locale::detect(&texts).code, "pt");
+
+        let texts = [("\u{200e}تصویر خارج کر دی گئی", true)];
+        assert_eq!(Locale::detect(&texts).code, "ur");
+    }
+
+    #[test]
+    fn detect_looks_for_events_in_system_lines_only() {
+        let texts = [("Ele saiu", false), ("Ela saiu", false), ("I left", false)];
+        assert_eq!(Locale::detect(&texts).code, "en");
+
+        let texts = [("Ele saiu", true)];
+        assert_eq!(Locale::detect(&texts).code, "pt");
+    }
+
+    #[test]
+    fn detect_falls_back_to_english() {
+        assert_eq!(Locale::detect(&[("lorem ipsum", false)]).code, "en");
+        assert_eq!(Locale::detect(&[]).code, "en");
+    }
+}
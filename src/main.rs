@@ -1,118 +1,542 @@
 #[macro_use]
 extern crate lazy_static;
 extern crate chrono;
+extern crate chrono_tz;
+extern crate csv;
 extern crate docopt;
 extern crate regex;
 extern crate serde_json;
+extern crate toml;
+extern crate unicode_normalization;
+extern crate zip;
 
 #[macro_use]
 extern crate serde_derive;
 
+mod alias;
+mod archive;
+mod attachment;
 mod conversation;
+mod dialect;
+mod discord;
 mod emoji;
+mod encoding;
+mod event;
+mod format;
+mod html;
+mod locale;
 mod message;
+mod ndjson;
+mod report;
+mod slack;
+mod stream;
+mod summary;
+mod table;
+mod telegram;
+mod zone;
 
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
-use std::fs;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Seek, Write};
+use std::path::Path;
 use std::process;
 
 use docopt::Docopt;
+use zip::result::ZipError;
 
-use conversation::{Conversation, Frequency, Timeline, TimelineType};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+
+use alias::Aliases;
+use conversation::{
+    Conversation, Edits, Frequency, Media, ParseOptions, Timeline, TimelineType, Week,
+};
+use dialect::Dialect;
 use emoji::Emojis;
-use message::Message;
+use locale::Locale;
+use message::{Message, MessageError, MessageErrorKind};
+use stream::{Diagnostic, Merge, Messages};
+use summary::Summary;
 
 #[derive(Debug)]
 enum AppErrorKind {
     FileNotFound,
     InvalidFile,
+    InvalidArchive,
+    InvalidExport,
+    MissingChat,
     InvalidHistory,
+    AmbiguousDate,
+    EmptyHistory,
+    InvalidAliases,
+    UnwritableOutput,
+    InvalidLine(Diagnostic),
 }
 
 #[derive(Debug)]
 struct AppError(AppErrorKind);
 
-// TODO: use `error::Error`
-impl Error for AppError {
-    fn description(&self) -> &str {
-        match self.0 {
+impl Error for AppError {}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
             AppErrorKind::FileNotFound => "file not found",
             AppErrorKind::InvalidFile => "invalid file contents",
+            AppErrorKind::InvalidArchive => "invalid zip archive",
+            AppErrorKind::InvalidExport => "invalid chat export",
+            AppErrorKind::MissingChat => "no chat found in zip archive",
             AppErrorKind::InvalidHistory => "invalid chat history",
+            AppErrorKind::AmbiguousDate => "ambiguous date format, use --date-format to pick one",
+            AppErrorKind::EmptyHistory => "no messages found in chat history",
+            AppErrorKind::InvalidAliases => "invalid alias file",
+            AppErrorKind::UnwritableOutput => "unable to write output",
+            AppErrorKind::InvalidLine(diagnostic) => {
+                return write!(f, "invalid chat history at {}", diagnostic)
+            }
         }
+        .fmt(f)
     }
 }
 
-impl fmt::Display for AppError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.description().fmt(f)
-    }
-}
-
+// the output is stable: the same chat gives byte for byte the same json. fields come in the
+// order they're declared here, hours from `00h` to `23h`, the timeline in chronological order,
+// emojis from the most used one with ties in code point order, participants from the most to
+// the least active one with ties by name, events and diagnostics as they appear in the chat
 #[derive(Debug, Serialize)]
 struct Insights<'is> {
     first: Option<&'is Message>,
     last: Option<&'is Message>,
     duration: String,
     frequency: Frequency,
+    #[serde(serialize_with = "conversation::by_activity")]
     frequency_per_participant: HashMap<String, Frequency>,
     total_messages: usize,
     total_words: usize,
     total_letters: usize,
     avg_words_per_message: f32,
     avg_letters_per_message: f32,
-    participants: &'is Vec<String>,
+    participants: Vec<String>,
     timeline: Timeline,
+    #[serde(serialize_with = "emoji::by_rank")]
     emojis: Emojis,
+    media: Media,
+    edits: Edits,
+    events: &'is Vec<Message>,
+    // only there when lenient
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<&'is Vec<Diagnostic>>,
 }
 
 impl Insights<'_> {
-    fn new<'is>(cnv: &'is Conversation, tl_type: TimelineType) -> Insights<'is> {
-        let (avg_words_per_message, avg_letters_per_message) = cnv.average();
-        let mut frequency_per_participant = HashMap::new();
-        let participants = cnv.participants();
-
-        for p in participants {
-            frequency_per_participant
-                .insert(p.to_string(), cnv.by_author(p.to_string()).frequency());
-        }
+    fn new<'is>(
+        summary: &'is Summary,
+        tl_type: TimelineType,
+        diagnostics: Option<&'is Vec<Diagnostic>>,
+    ) -> Insights<'is> {
+        let (avg_words_per_message, avg_letters_per_message) = summary.average();
 
         Insights {
-            first: cnv.first(),
-            last: cnv.last(),
-            duration: cnv.duration().unwrap().to_string(),
-            frequency: cnv.frequency(),
-            total_messages: cnv.count(),
-            total_words: cnv.words(),
-            total_letters: cnv.letters(),
+            first: summary.first(),
+            last: summary.last(),
+            duration: summary.duration().unwrap().to_string(),
+            frequency: summary.frequency(),
+            total_messages: summary.count(),
+            total_words: summary.words(),
+            total_letters: summary.letters(),
             avg_words_per_message,
             avg_letters_per_message,
-            participants,
-            frequency_per_participant,
-            timeline: cnv.timeline(tl_type),
-            emojis: cnv.emojis(),
+            participants: summary
+                .leaderboard()
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect(),
+            frequency_per_participant: summary.frequency_per_participant(),
+            timeline: summary.timeline(tl_type),
+            emojis: summary.emojis(),
+            media: summary.media(),
+            edits: summary.edits(),
+            events: summary.events(),
+            diagnostics,
+        }
+    }
+}
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+// whatsapp only exports plain text, json files come from telegram desktop or
+// DiscordChatExporter
+fn is_json(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+// messages as `export` writes them, one json object per line
+fn is_ndjson(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ndjson") || ext.eq_ignore_ascii_case("jsonl"))
+}
+
+type Source = Box<dyn Iterator<Item = Result<Message, AppError>>>;
+
+fn records<R: BufRead + 'static>(reader: R, filename: &str) -> Source {
+    let filename = filename.to_string();
+
+    Box::new(ndjson::Records::new(reader).map(move |m| m.map_err(|d| line_error(&filename, d))))
+}
+
+// whether the leading bytes of an input start a zip archive
+fn is_zip<R: BufRead>(reader: &mut R) -> Result<bool, AppError> {
+    match reader.fill_buf() {
+        Ok(head) => Ok(head.starts_with(ZIP_MAGIC)),
+        Err(_) => Err(AppError(AppErrorKind::InvalidFile)),
+    }
+}
+
+// text inputs are turned into utf-8 before anything else, the leading bytes then tell whether
+// it's a json export
+fn decode<R: BufRead + 'static>(reader: R) -> Result<(Box<dyn BufRead>, bool), AppError> {
+    let mut reader = match encoding::decode(reader) {
+        Ok(reader) => reader,
+        Err(_) => return Err(AppError(AppErrorKind::InvalidFile)),
+    };
+
+    match reader.fill_buf() {
+        Ok(head) => {
+            let json = head.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{');
+            Ok((reader, json))
         }
+        Err(_) => Err(AppError(AppErrorKind::InvalidFile)),
     }
 }
 
-fn execute(filename: String, timeline_type: TimelineType) -> Result<(), AppError> {
-    let contents = match fs::read_to_string(filename) {
-        Ok(contents) => contents,
+// `-` reads from stdin, directories are taken for a channel of a slack export
+fn open(filename: &str, options: &ParseOptions) -> Result<Source, AppError> {
+    if filename == "-" {
+        let mut reader = BufReader::new(io::stdin());
+
+        // zip archives can't be read without seeking, so these are buffered as a whole
+        if is_zip(&mut reader)? {
+            let mut bytes = Vec::new();
+            if reader.read_to_end(&mut bytes).is_err() {
+                return Err(AppError(AppErrorKind::InvalidFile));
+            }
+
+            return unzip(io::Cursor::new(bytes), filename, options);
+        }
+
+        let (reader, json) = decode(reader)?;
+        return read(reader, json, filename, options);
+    }
+
+    let path = Path::new(filename);
+    if path.is_dir() {
+        return match slack::read(path) {
+            Ok(conversation) => Ok(Box::new(conversation.into_messages().into_iter().map(Ok))),
+            Err(err) => match err.kind() {
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => {
+                    Err(AppError(AppErrorKind::InvalidExport))
+                }
+                _ => Err(AppError(AppErrorKind::InvalidFile)),
+            },
+        };
+    }
+
+    let mut reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
         Err(err) => match err.kind() {
             io::ErrorKind::NotFound => return Err(AppError(AppErrorKind::FileNotFound)),
             _ => return Err(AppError(AppErrorKind::InvalidFile)),
         },
     };
 
-    let conversation = match Conversation::from_str(&contents) {
-        Ok(conversation) => conversation,
-        Err(_) => return Err(AppError(AppErrorKind::InvalidHistory)),
+    if is_zip(&mut reader)? {
+        return unzip(reader, filename, options);
+    }
+
+    let (reader, json) = decode(reader)?;
+    if is_ndjson(filename) {
+        return Ok(records(reader, filename));
+    }
+
+    read(reader, json || is_json(filename), filename, options)
+}
+
+fn unzip<R: Read + Seek>(
+    reader: R,
+    filename: &str,
+    options: &ParseOptions,
+) -> Result<Source, AppError> {
+    let archive = match archive::read(reader) {
+        Ok(archive) => archive,
+        Err(ZipError::FileNotFound) => return Err(AppError(AppErrorKind::MissingChat)),
+        Err(_) => return Err(AppError(AppErrorKind::InvalidArchive)),
     };
 
-    let insights = Insights::new(&conversation, timeline_type);
+    // attachments get their size from the files shipped along with the chat
+    let files = archive.files;
+    let filename = filename.to_string();
+    let messages = Messages::new(io::Cursor::new(archive.chat.into_bytes()), options)
+        .map_err(history_error)?
+        .map(move |message| {
+            let mut message = message.map_err(|d| line_error(&filename, d))?;
+            message.resolve_attachment(&files);
+
+            Ok(message)
+        });
+
+    Ok(Box::new(messages))
+}
+
+fn read<R: BufRead + 'static>(
+    mut reader: R,
+    json: bool,
+    filename: &str,
+    options: &ParseOptions,
+) -> Result<Source, AppError> {
+    if !json {
+        let filename = filename.to_string();
+        let messages = Messages::new(reader, options).map_err(history_error)?;
+        return Ok(Box::new(
+            messages.map(move |m| m.map_err(|d| line_error(&filename, d))),
+        ));
+    }
+
+    let mut contents = String::new();
+    if reader.read_to_string(&mut contents).is_err() {
+        return Err(AppError(AppErrorKind::InvalidFile));
+    }
+
+    // what `export` wrote comes without a name that tells it apart when it's piped in
+    let is_records = |contents: &str| {
+        ndjson::Records::new(contents.as_bytes())
+            .next()
+            .is_some_and(|m| m.is_ok())
+    };
+
+    match telegram::parse(&contents, &options.zone.unwrap_or(Tz::UTC))
+        .or_else(|_| discord::parse(&contents))
+    {
+        Ok(conversation) => Ok(Box::new(conversation.into_messages().into_iter().map(Ok))),
+        Err(_) if is_records(&contents) => {
+            Ok(records(io::Cursor::new(contents.into_bytes()), filename))
+        }
+        Err(_) => Err(AppError(AppErrorKind::InvalidExport)),
+    }
+}
+
+// without a file only the phone numbers announced in the chat are merged
+fn load_aliases(filename: Option<String>) -> Result<Aliases, AppError> {
+    let filename = match filename {
+        Some(filename) => filename,
+        None => return Ok(Aliases::default()),
+    };
+
+    Aliases::read(Path::new(&filename)).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => AppError(AppErrorKind::FileNotFound),
+        io::ErrorKind::InvalidData => AppError(AppErrorKind::InvalidAliases),
+        _ => AppError(AppErrorKind::InvalidFile),
+    })
+}
+
+// what every message goes through before it's counted
+struct Pipeline {
+    // lines that can't be parsed fail the whole run unless it's lenient, then they're set aside
+    lenient: bool,
+    diagnostics: Vec<Diagnostic>,
+    // names are left as parsed without
+    aliases: Option<Aliases>,
+    // zone the report is made for, messages keep their own offset without one
+    zone: Option<Tz>,
+}
+
+impl Pipeline {
+    fn process(&mut self, message: Result<Message, AppError>) -> Result<Option<Message>, AppError> {
+        let mut message = match message {
+            Ok(message) => message,
+            Err(AppError(AppErrorKind::InvalidLine(diagnostic))) if self.lenient => {
+                self.diagnostics.push(diagnostic);
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+
+        if let Some(zone) = &self.zone {
+            zone::convert(&mut message, zone);
+        }
+        if let Some(aliases) = &mut self.aliases {
+            aliases.apply(&mut message);
+        }
+
+        Ok(Some(message))
+    }
+}
+
+// overlapping exports are loaded one after the other and lined up, later inputs win where the
+// same message differs
+fn combine(
+    filenames: &[String],
+    options: &ParseOptions,
+    pipeline: &mut Pipeline,
+) -> Result<Conversation, AppError> {
+    let mut combined = Conversation::from_messages(Vec::new(), Vec::new());
+
+    for filename in filenames {
+        let mut messages = Vec::new();
+        for message in open(filename, options)? {
+            messages.extend(pipeline.process(message)?);
+        }
+
+        for conflict in combined.merge(messages.into_iter().collect()) {
+            eprintln!("warning: {}", conflict);
+        }
+    }
+
+    Ok(combined)
+}
+
+// what the analysis is printed as
+#[derive(Clone, Debug, PartialEq)]
+enum Output {
+    Json,
+    Pretty,
+    Html,
+    Table(Tables),
+}
+
+// `--format csv` or `tsv`, every table is written to a file in `out_dir`, or the one picked is
+// printed
+#[derive(Clone, Debug, PartialEq)]
+struct Tables {
+    delimiter: u8,
+    extension: &'static str,
+    out_dir: Option<String>,
+    name: Option<String>,
+}
+
+impl Output {
+    fn from_name(name: &str) -> Option<Output> {
+        let tables = |delimiter, extension| {
+            Some(Output::Table(Tables {
+                delimiter,
+                extension,
+                out_dir: None,
+                name: None,
+            }))
+        };
+
+        match name {
+            "json" => Some(Output::Json),
+            "pretty" => Some(Output::Pretty),
+            "html" => Some(Output::Html),
+            "csv" => tables(b',', "csv"),
+            "tsv" => tables(b'\t', "tsv"),
+            _ => None,
+        }
+    }
+}
+
+fn write_tables(
+    summary: &Summary,
+    timeline_type: TimelineType,
+    tables: &Tables,
+) -> Result<(), AppError> {
+    fn unwritable<E>(_: E) -> AppError {
+        AppError(AppErrorKind::UnwritableOutput)
+    }
+
+    let picked = table::build(summary, timeline_type)
+        .into_iter()
+        .filter(|t| tables.name.as_ref().is_none_or(|name| name == t.name));
+
+    match &tables.out_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir).map_err(unwritable)?;
+
+            for t in picked {
+                let path = Path::new(dir).join(format!("{}.{}", t.name, tables.extension));
+                let file = File::create(path).map_err(unwritable)?;
+                t.write(file, tables.delimiter).map_err(unwritable)?;
+            }
+        }
+        None => {
+            for t in picked {
+                t.write(io::stdout(), tables.delimiter)
+                    .map_err(unwritable)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// every input is read as it goes and merged with the others in chronological order, so the
+// history never sits in memory as a whole. overlapping exports need to be lined up first, which
+// takes them in as a whole
+fn execute(
+    filenames: Vec<String>,
+    options: ParseOptions,
+    timeline_type: TimelineType,
+    dedupe: bool,
+    output: Output,
+    mut pipeline: Pipeline,
+) -> Result<(), AppError> {
+    let summary = if dedupe {
+        Summary::from(&combine(&filenames, &options, &mut pipeline)?)
+    } else {
+        let sources = filenames
+            .iter()
+            .map(|filename| open(filename, &options))
+            .collect::<Result<Vec<Source>, AppError>>()?;
+
+        let mut summary = Summary::default();
+        for message in Merge::new(sources) {
+            if let Some(message) = pipeline.process(message)? {
+                summary.add(message);
+            }
+        }
+
+        summary
+    };
+
+    if summary.count() == 0 {
+        return Err(AppError(AppErrorKind::EmptyHistory));
+    }
+
+    if output != Output::Json {
+        for diagnostic in pipeline.diagnostics.iter() {
+            eprintln!("warning: skipped {}", diagnostic);
+        }
+    }
+
+    match output {
+        Output::Pretty => {
+            let style = report::Style {
+                color: io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            };
+            print!("{}", report::render(&summary, timeline_type, style));
+
+            return Ok(());
+        }
+        Output::Html => {
+            print!("{}", html::render(&summary, timeline_type));
+
+            return Ok(());
+        }
+        Output::Table(tables) => return write_tables(&summary, timeline_type, &tables),
+        Output::Json => {}
+    }
+
+    let diagnostics = if pipeline.lenient {
+        Some(&pipeline.diagnostics)
+    } else {
+        None
+    };
+    let insights = Insights::new(&summary, timeline_type, diagnostics);
     println!(
         "{}",
         serde_json::to_string(&insights).expect("unable to parse json")
@@ -121,31 +545,197 @@ fn execute(filename: String, timeline_type: TimelineType) -> Result<(), AppError
     Ok(())
 }
 
-const USAGE: &'static str = "
+fn execute_merge(
+    filenames: Vec<String>,
+    options: ParseOptions,
+    mut pipeline: Pipeline,
+) -> Result<(), AppError> {
+    let conversation = combine(&filenames, &options, &mut pipeline)?;
+
+    for diagnostic in pipeline.diagnostics {
+        eprintln!("warning: skipped {}", diagnostic);
+    }
+
+    if conversation.count() == 0 {
+        return Err(AppError(AppErrorKind::EmptyHistory));
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string(&conversation).expect("unable to parse json")
+    );
+
+    Ok(())
+}
+
+// every message on a line of its own, system ones included
+fn execute_export(
+    filenames: Vec<String>,
+    options: ParseOptions,
+    dedupe: bool,
+    mut pipeline: Pipeline,
+) -> Result<(), AppError> {
+    let stdout = io::stdout();
+    let mut writer = io::BufWriter::new(stdout.lock());
+    let mut count = 0;
+
+    let mut write = |message: &Message| {
+        count += 1;
+        ndjson::write(&mut writer, message).map_err(|_| AppError(AppErrorKind::UnwritableOutput))
+    };
+
+    if dedupe {
+        for message in combine(&filenames, &options, &mut pipeline)?.into_messages() {
+            write(&message)?;
+        }
+    } else {
+        let sources = filenames
+            .iter()
+            .map(|filename| open(filename, &options))
+            .collect::<Result<Vec<Source>, AppError>>()?;
+
+        for message in Merge::new(sources) {
+            if let Some(message) = pipeline.process(message)? {
+                write(&message)?;
+            }
+        }
+    }
+
+    for diagnostic in pipeline.diagnostics {
+        eprintln!("warning: skipped {}", diagnostic);
+    }
+
+    if count == 0 {
+        return Err(AppError(AppErrorKind::EmptyHistory));
+    }
+
+    writer
+        .flush()
+        .map_err(|_| AppError(AppErrorKind::UnwritableOutput))
+}
+
+fn line_error(filename: &str, mut diagnostic: Diagnostic) -> AppError {
+    match diagnostic.error.0 {
+        MessageErrorKind::Unreadable => AppError(AppErrorKind::InvalidFile),
+        _ => {
+            diagnostic.file = Some(filename.to_string());
+            AppError(AppErrorKind::InvalidLine(diagnostic))
+        }
+    }
+}
+
+fn history_error(err: MessageError) -> AppError {
+    match err.0 {
+        MessageErrorKind::AmbiguousDate => AppError(AppErrorKind::AmbiguousDate),
+        MessageErrorKind::Unreadable => AppError(AppErrorKind::InvalidFile),
+        _ => AppError(AppErrorKind::InvalidHistory),
+    }
+}
+
+const USAGE: &str = "
 Insights - A minimalistic whatsapp chat analyser.
 
+Both iOS and Android exports are supported, the format is detected automatically.
+Exports made with media can be passed as the zip archive WhatsApp creates.
+Telegram Desktop exports are read from their `result.json` and DiscordChatExporter ones from
+their json file, a channel of a Slack export is read from its directory.
+Pass `-` to read from stdin, several inputs are analysed as one conversation.
+`merge` lines up exports that overlap, drops the messages found in more than one of them and
+prints the combined conversation, differing versions of a message are reported as warnings.
+`export` prints every message as a json object on a line of its own (NDJSON), which is read
+back like any other export from a `.ndjson` or `.jsonl` file or from stdin. Authors are written
+as parsed unless `--aliases` is passed.
+A line that can't be parsed stops the analysis and is reported with its position, unless
+`--lenient` is passed: then it's skipped and listed under `diagnostics`.
+Participants are told apart by name, phone numbers are compared in their E.164 form and a
+number someone changed to is counted as theirs. An alias file maps more names to one person,
+json `{\"Foo Bar\": [\"Foo\", \"+44 7700 900123\"]}` or toml `\"Foo Bar\" = [\"Foo\"]`.
+
+Timestamps of WhatsApp and older Telegram exports are taken as UTC unless `--source-tz` names
+the zone of the phone, all of them are reported with their offset in the zone `--report-tz`
+names, which defaults to the source one.
+
 Usage:
-    insights <file> [--pretty] [--timeline=<duration>]
+    insights merge <file>... [options]
+    insights export <file>... [--merge] [options]
+    insights <file>... [--pretty | --format=<format>] [--out-dir=<dir>] [--table=<name>] [--merge] [--timeline=<duration>] [--anchor=<time>] [--week-start=<day>] [--iso-weeks] [options]
     insights (-h | --help)
     insights --version
 
 Options:
     -h --help                   shows this usage
     --version                   shows the version of application
-    --pretty                    prints the analysis in pretty format 
+    --pretty                    prints a report to read instead of the json, in colour when
+                                printed to a terminal
+    --format=<format>           sets what the analysis is printed as [default: json]
+                                options:
+                                    - json
+                                    - pretty  same as --pretty
+                                    - html    a page with charts that works offline
+                                    - csv     tables with a row per period, hour, participant
+                                              or emoji, needs --out-dir or --table
+                                    - tsv     same as csv, separated by tabs
+    --out-dir=<dir>             writes every table to a file in the directory, e.g. timeline.csv
+    --table=<name>              prints only one table, or writes only it with --out-dir
+                                options: timeline, frequency, participants, emojis
+    --merge                     drops messages found in more than one of the inputs
+    --lenient                   skips lines that can't be parsed instead of stopping at them
+    --aliases=<file>            reads the names participants go by from a json or toml file
     --timeline=<duration>       sets the duration of the timeline [default: monthly]
                                 options:
+                                    - hourly
                                     - daily     
                                     - weekly
                                     - monthly
+                                    - quarterly
                                     - yearly
+                                    - an interval in hours, days or weeks, e.g. 6h, 14d or 2w
+    --anchor=<time>             sets where intervals are counted from instead of midnight of
+                                the first day, on the hour, e.g. 2019-09-11 or 2019-09-11T06:00
+    --week-start=<day>          sets the day weeks start on, e.g. mon, sun or sat [default: mon]
+    --iso-weeks                 labels weeks with their ISO year and week, e.g. 2019-W37
+    --date-format=<format>      sets the order of timestamps instead of detecting it
+                                options:
+                                    - ymd   (2019-09-11, 01:57:17)
+                                    - dmy   (11/09/2019, 01:57 or 11.09.19, 01:57:17)
+                                    - mdy   (9/11/19, 1:57 PM)
+                                    - a strftime pattern, e.g. \"%d-%m-%Y %H:%M\"
+    --lang=<code>               sets the language of the phone that made the export instead of
+                                detecting it
+                                options: en, de, es, ur, pt
+    --source-tz=<zone>          sets the time zone of the phone that made the export, e.g.
+                                Europe/Berlin
+    --report-tz=<zone>          sets the time zone the analysis is made for
 ";
 
+// `2019-09-11` or `2019-09-11T06:00`, in the zone of the report
+fn parse_anchor(raw: &str) -> Option<NaiveDateTime> {
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .map(|date| date.and_time(NaiveTime::MIN))
+        .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M"))
+        .ok()
+}
+
 #[derive(Debug, Deserialize)]
 struct Args {
-    arg_file: String,
+    cmd_merge: bool,
+    cmd_export: bool,
+    arg_file: Vec<String>,
     flag_pretty: bool,
+    flag_format: String,
+    flag_out_dir: Option<String>,
+    flag_table: Option<String>,
+    flag_merge: bool,
+    flag_lenient: bool,
+    flag_aliases: Option<String>,
+    flag_source_tz: Option<String>,
+    flag_report_tz: Option<String>,
     flag_timeline: String,
+    flag_anchor: Option<String>,
+    flag_week_start: String,
+    flag_iso_weeks: bool,
+    flag_date_format: Option<String>,
+    flag_lang: Option<String>,
 }
 
 fn main() {
@@ -153,11 +743,17 @@ fn main() {
         .and_then(|d| d.deserialize())
         .unwrap_or_else(|e| e.exit());
 
-    let timeline_type = match args.flag_timeline.as_str() {
-        "daily" => TimelineType::Daily,
-        "weekly" => TimelineType::Weekly,
-        "monthly" => TimelineType::Monthly,
-        "yearly" => TimelineType::Yearly,
+    let timeline_type = match &args.flag_anchor {
+        Some(raw) => parse_anchor(raw)
+            .and_then(|anchor| TimelineType::from_name(&args.flag_timeline, Some(anchor))),
+        None => TimelineType::from_name(&args.flag_timeline, None),
+    };
+    let week = args.flag_week_start.parse().ok().map(|start| Week {
+        start,
+        iso: args.flag_iso_weeks,
+    });
+    let timeline_type = match (timeline_type, week) {
+        (Some(timeline_type), Some(week)) => timeline_type.with_week(week),
         _ => {
             println!("Invalid Arguments");
             println!("{}", USAGE);
@@ -165,11 +761,167 @@ fn main() {
         }
     };
 
-    process::exit(match execute(args.arg_file, timeline_type) {
+    let output = match (Output::from_name(&args.flag_format), &args.flag_table) {
+        (Some(_), None) if args.flag_pretty && args.flag_out_dir.is_none() => Some(Output::Pretty),
+        (Some(Output::Table(tables)), name) => {
+            let name_is_known = name
+                .as_ref()
+                .is_none_or(|n| table::NAMES.contains(&n.as_str()));
+
+            if name_is_known && (name.is_some() || args.flag_out_dir.is_some()) {
+                Some(Output::Table(Tables {
+                    out_dir: args.flag_out_dir.clone(),
+                    name: name.clone(),
+                    ..tables
+                }))
+            } else {
+                None
+            }
+        }
+        (Some(output), None) if args.flag_out_dir.is_none() => Some(output),
+        _ => None,
+    };
+    let output = match output {
+        Some(output) => output,
+        None => {
+            println!("Invalid Arguments");
+            println!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let locale = match args.flag_lang {
+        Some(code) => match Locale::find(&code) {
+            Some(locale) => Some(locale),
+            None => {
+                println!("Invalid Arguments");
+                println!("{}", USAGE);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let dialect = match args.flag_date_format {
+        Some(name) => match Dialect::from_name(&name) {
+            Some(dialect) => Some(dialect),
+            None => {
+                println!("Invalid Arguments");
+                println!("{}", USAGE);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let zone = |name: Option<String>| match name {
+        Some(name) => match zone::find(&name) {
+            Some(zone) => Some(zone),
+            None => {
+                println!("Invalid Arguments");
+                println!("{}", USAGE);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let source_zone = zone(args.flag_source_tz);
+    let report_zone = zone(args.flag_report_tz);
+
+    let options = ParseOptions {
+        dialect,
+        locale,
+        zone: source_zone,
+        ..ParseOptions::default()
+    };
+
+    // `export` writes authors as parsed, so reading it back doesn't apply aliases twice. it only
+    // applies them when an alias file is passed
+    let aliased = !args.cmd_export || args.flag_aliases.is_some();
+
+    let result = match load_aliases(args.flag_aliases) {
+        Ok(aliases) => {
+            let pipeline = Pipeline {
+                lenient: args.flag_lenient,
+                diagnostics: Vec::new(),
+                aliases: if aliased { Some(aliases) } else { None },
+                zone: report_zone.or(source_zone),
+            };
+
+            if args.cmd_merge {
+                execute_merge(args.arg_file, options, pipeline)
+            } else if args.cmd_export {
+                execute_export(args.arg_file, options, args.flag_merge, pipeline)
+            } else {
+                execute(
+                    args.arg_file,
+                    options,
+                    timeline_type,
+                    args.flag_merge,
+                    output,
+                    pipeline,
+                )
+            }
+        }
+        Err(err) => Err(err),
+    };
+
+    process::exit(match result {
         Ok(_) => 0,
         Err(err) => {
-            eprintln!("error: {:?}", err);
+            eprintln!("error: {}", err);
             1
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MOCK: &str = "
+[2019-09-11, 01:57:17] +44 7700 900123: Hey
+[2019-09-11, 01:58:17] Group: \u{200e}+44 7700 900123 changed to +44 7700 900456
+[2019-09-11, 01:59:17] +44 7700 900456: new number
+[2019-09-11, 02:00:00] Bar: ok
+";
+
+    fn process(aliases: Option<Aliases>, source: Source) -> Vec<Message> {
+        let mut pipeline = Pipeline {
+            lenient: false,
+            diagnostics: Vec::new(),
+            aliases,
+            zone: None,
+        };
+
+        source
+            .map(|m| pipeline.process(m).unwrap().unwrap())
+            .collect()
+    }
+
+    fn authors(messages: Vec<Message>) -> Vec<String> {
+        messages.into_iter().map(|m| m.author).collect()
+    }
+
+    #[test]
+    fn export_round_trips_messages_as_parsed() {
+        let parsed = Conversation::from_str(MOCK).unwrap().into_messages();
+        let aliases = || Aliases::from_json(r#"{"Foo": ["+44 7700 900123"]}"#).ok();
+
+        let mut out = Vec::new();
+        for message in process(None, Box::new(parsed.clone().into_iter().map(Ok))) {
+            ndjson::write(&mut out, &message).unwrap();
+        }
+
+        let imported = process(None, records(io::Cursor::new(out.clone()), "chat.ndjson"));
+        assert_eq!(imported, parsed);
+
+        let analysed = process(aliases(), Box::new(parsed.into_iter().map(Ok)));
+        let reimported = process(aliases(), records(io::Cursor::new(out), "chat.ndjson"));
+        assert_eq!(authors(reimported), authors(analysed));
+        assert_eq!(
+            authors(imported),
+            vec!["+44 7700 900123", "Group", "+44 7700 900456", "Bar"]
+        );
+    }
+}
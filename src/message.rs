@@ -1,50 +1,207 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 
 use chrono::prelude::*;
+use unicode_normalization::UnicodeNormalization;
 
+use crate::attachment::Attachment;
+use crate::dialect::Dialect;
 use crate::emoji::EMOJI;
+use crate::event::ChatEvent;
+use crate::locale::Locale;
+use crate::zone;
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MessageType {
     Image,
     Text,
     Audio,
     Video,
     Contact,
+    Sticker,
+    Gif,
+    Document(String),
+    Location,
+    LiveLocation,
+    Poll,
+    Call { video: bool, missed: bool },
+    Deleted,
+    ViewOnce,
+    System,
     Unknown,
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl MessageType {
+    // exports prefix markers with an invisible U+200E, only the exact strings are matched so a
+    // message like `I omitted the image` stays a text
+    pub fn detect(text: &str, locale: &Locale) -> MessageType {
+        let text = text.trim();
+        let text = text.strip_prefix('\u{200e}').unwrap_or(text);
+
+        let markers = [
+            (locale.image, MessageType::Image),
+            (locale.audio, MessageType::Audio),
+            (locale.video, MessageType::Video),
+            (locale.contact, MessageType::Contact),
+            (locale.sticker, MessageType::Sticker),
+            (locale.gif, MessageType::Gif),
+            (locale.media, MessageType::Unknown),
+        ];
+
+        if let Some((_, kind)) = markers.iter().find(|(marker, _)| *marker == text) {
+            return kind.clone();
+        }
+
+        if locale.deleted.contains(&text) {
+            return MessageType::Deleted;
+        }
+
+        if locale.view_once.contains(&text) {
+            return MessageType::ViewOnce;
+        }
+
+        if locale.live_location.contains(&text) {
+            return MessageType::LiveLocation;
+        }
+
+        let patterns = locale.patterns();
+
+        if patterns.location.is_match(text) {
+            return MessageType::Location;
+        }
+
+        if text.starts_with(&format!("{}\n", locale.poll)) {
+            return MessageType::Poll;
+        }
+
+        if let Some(c) = patterns.document.captures(text) {
+            return MessageType::Document(c["name"].to_string());
+        }
+
+        let calls = [
+            (locale.missed_voice_call, false, true),
+            (locale.missed_video_call, true, true),
+            (locale.voice_call, false, false),
+            (locale.video_call, true, false),
+        ];
+
+        for (marker, video, missed) in calls.iter() {
+            if is_call(text, marker) {
+                return MessageType::Call {
+                    video: *video,
+                    missed: *missed,
+                };
+            }
+        }
+
+        MessageType::Text
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MessageType::Image => "image",
+            MessageType::Text => "text",
+            MessageType::Audio => "audio",
+            MessageType::Video => "video",
+            MessageType::Contact => "contact",
+            MessageType::Sticker => "sticker",
+            MessageType::Gif => "gif",
+            MessageType::Document(_) => "document",
+            MessageType::Location => "location",
+            MessageType::LiveLocation => "live_location",
+            MessageType::Poll => "poll",
+            MessageType::Call { .. } => "call",
+            MessageType::Deleted => "deleted",
+            MessageType::ViewOnce => "view_once",
+            MessageType::System => "system",
+            MessageType::Unknown => "unknown",
+        }
+    }
+
+    // messages that stand for a shared file, whether or not it made it into the export
+    pub fn is_media(&self) -> bool {
+        matches!(
+            self,
+            MessageType::Image
+                | MessageType::Audio
+                | MessageType::Video
+                | MessageType::Contact
+                | MessageType::Sticker
+                | MessageType::Gif
+                | MessageType::Document(_)
+                | MessageType::ViewOnce
+                | MessageType::Unknown
+        )
+    }
+}
+
+// `Voice call`, `Voice call, 12 min` or `Voice call • No answer`
+fn is_call(text: &str, marker: &str) -> bool {
+    let text = text.to_lowercase();
+
+    match text.strip_prefix(&marker.to_lowercase()) {
+        Some(rest) => rest.is_empty() || rest.starts_with(',') || rest.starts_with(" •"),
+        None => false,
+    }
+}
+
+// phones set to a right-to-left language scatter directional marks through names and texts,
+// these are dropped and what's left is composed so the same name always reads the same
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !is_directional(*c))
+        .nfc()
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn is_directional(c: char) -> bool {
+    matches!(c, '\u{200e}' | '\u{200f}' | '\u{061c}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
-    pub datetime: NaiveDateTime,
+    pub datetime: DateTime<FixedOffset>,
     pub author: String,
     pub text: String,
     pub kind: MessageType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<ChatEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<Attachment>,
+    #[serde(default)]
+    pub edited: bool,
+    // timestamp of the message this one replies to within a thread
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<DateTime<FixedOffset>>,
 }
 
 #[derive(Debug)]
 pub enum MessageErrorKind {
     InvalidDate,
+    AmbiguousDate,
     EmptyMessage,
+    Unreadable,
+    InvalidRecord,
 }
 
 #[derive(Debug)]
 pub struct MessageError(pub MessageErrorKind);
 
-// TODO: use `error::Error`
-impl Error for MessageError {
-    fn description(&self) -> &str {
+impl Error for MessageError {}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.0 {
             MessageErrorKind::InvalidDate => "unable to parse date",
+            MessageErrorKind::AmbiguousDate => "ambiguous date format",
             MessageErrorKind::EmptyMessage => "empty message",
+            MessageErrorKind::Unreadable => "unable to read chat history",
+            MessageErrorKind::InvalidRecord => "invalid message record",
         }
-    }
-}
-
-impl fmt::Display for MessageError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.description().fmt(f)
+        .fmt(f)
     }
 }
 
@@ -52,36 +209,88 @@ pub type Result<T> = ::std::result::Result<T, MessageError>;
 
 impl Message {
     pub fn from_str(datetime: &str, author: &str, text: &str) -> Result<Message> {
-        let datetime = match NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d, %H:%M:%S") {
-            Ok(v) => v,
-            Err(_) => return Err(MessageError(MessageErrorKind::InvalidDate)),
+        Self::parse(datetime, author, text, &Dialect::YearMonthDay)
+    }
+
+    // timestamps are taken as utc
+    pub fn parse(datetime: &str, author: &str, text: &str, dialect: &Dialect) -> Result<Message> {
+        Ok(Self::new(
+            zone::utc(&dialect.parse(datetime)?),
+            author,
+            text,
+            Locale::english(),
+        ))
+    }
+
+    pub fn new(
+        datetime: DateTime<FixedOffset>,
+        author: &str,
+        text: &str,
+        locale: &Locale,
+    ) -> Message {
+        let text = normalize(text);
+        let text = text.as_str();
+
+        // newer exports append a marker to edited messages, it's not part of the text
+        let edited = locale.patterns().edited.find(text);
+        let text = match edited {
+            Some(marker) => &text[..marker.start()],
+            None => text,
         };
 
-        let kind = if text.contains("omitted") {
-            if text.contains("image") {
-                MessageType::Image
-            } else if text.contains("audio") {
-                MessageType::Audio
-            } else if text.contains("video") {
-                MessageType::Video
-            } else if text.contains("card") {
-                MessageType::Contact
-            } else {
-                MessageType::Unknown
-            }
-        } else {
-            MessageType::Text
+        let attachment = Attachment::parse(text, locale);
+
+        let kind = match &attachment {
+            Some(attachment) => attachment.kind(),
+            None => MessageType::detect(text, locale),
         };
 
-        let author = String::from(author);
+        let author = normalize(author);
         let text = String::from(text.trim());
 
-        Ok(Message {
+        Message {
             datetime,
             author,
             text,
             kind,
-        })
+            event: None,
+            attachment,
+            edited: edited.is_some(),
+            reply_to: None,
+        }
+    }
+
+    // system lines carry the group name or nothing as their author
+    pub fn system(
+        datetime: DateTime<FixedOffset>,
+        author: &str,
+        text: &str,
+        locale: &Locale,
+    ) -> Message {
+        let text = normalize(text);
+
+        Message {
+            datetime,
+            author: normalize(author),
+            kind: MessageType::System,
+            event: ChatEvent::parse(&text, locale),
+            text,
+            attachment: None,
+            edited: false,
+            reply_to: None,
+        }
+    }
+
+    // takes the size of the attachment from the files shipped along with the chat, a size that
+    // came with the export itself is kept
+    pub fn resolve_attachment(&mut self, files: &HashMap<String, u64>) {
+        if let Some(attachment) = self.attachment.as_mut() {
+            attachment.size = files.get(&attachment.name).cloned().or(attachment.size);
+        }
+    }
+
+    pub fn is_media(&self) -> bool {
+        self.kind.is_media()
     }
 
     pub fn letters(&self) -> String {
@@ -107,7 +316,7 @@ mod tests {
     #[test]
     fn from_str_parses_date() {
         let m = Message::from_str("2019-09-11, 01:57:17", "Foo Bar", "Baz Qux").unwrap();
-        assert_eq!(format!("{}", m.datetime), "2019-09-11 01:57:17");
+        assert_eq!(format!("{}", m.datetime), "2019-09-11 01:57:17 +00:00");
     }
 
     #[test]
@@ -121,6 +330,18 @@ mod tests {
         let m = Message::from_str("2019-09-11, 01:57:17", "Foo Bar", "Baz Qux").unwrap();
         assert_eq!(m.text, "Baz Qux");
     }
+    #[test]
+    fn parse_works_with_dialect() {
+        let m = Message::parse(
+            "9/11/19, 1:57 PM",
+            "Foo Bar",
+            "Baz Qux",
+            &Dialect::MonthDayYear,
+        )
+        .unwrap();
+        assert_eq!(format!("{}", m.datetime), "2019-09-11 13:57:00 +00:00");
+    }
+
     #[test]
     fn from_str_identifies_kind() {
         let m_text = Message::from_str("2019-09-11, 01:57:17", "Foo Bar", "Baz Qux").unwrap();
@@ -143,6 +364,165 @@ mod tests {
         assert_eq!(m_contact.kind, MessageType::Contact);
     }
 
+    #[test]
+    fn detect_works() {
+        macro_rules! assert_detects {
+            ($text: expr, $kind: expr) => {
+                assert_eq!(
+                    MessageType::detect($text, Locale::english()),
+                    $kind,
+                    "with: {}",
+                    $text
+                );
+            };
+        }
+
+        assert_detects!("‎sticker omitted", MessageType::Sticker);
+        assert_detects!("‎GIF omitted", MessageType::Gif);
+        assert_detects!(
+            "report.pdf • ‎3 pages ‎document omitted",
+            MessageType::Document(String::from("report.pdf"))
+        );
+        assert_detects!(
+            "‎notes.txt ‎document omitted",
+            MessageType::Document(String::from("notes.txt"))
+        );
+        assert_detects!(
+            "‎Location: https://maps.google.com/?q=51.5,-0.1",
+            MessageType::Location
+        );
+        assert_detects!("‎Live location shared", MessageType::LiveLocation);
+        assert_detects!("‎POLL:\nLunch?\n‎OPTION: Pizza (1 vote)", MessageType::Poll);
+        assert_detects!(
+            "‎Missed voice call",
+            MessageType::Call {
+                video: false,
+                missed: true
+            }
+        );
+        assert_detects!(
+            "‎Video call, ‎12 min",
+            MessageType::Call {
+                video: true,
+                missed: false
+            }
+        );
+        assert_detects!("‎This message was deleted", MessageType::Deleted);
+        assert_detects!("‎view once image omitted", MessageType::ViewOnce);
+        assert_detects!("<Media omitted>", MessageType::Unknown);
+    }
+
+    #[test]
+    fn detect_works_for_other_locales() {
+        let de = Locale::find("de").unwrap();
+        assert_eq!(
+            MessageType::detect("‎Bild weggelassen", de),
+            MessageType::Image
+        );
+        assert_eq!(
+            MessageType::detect("Verpasster Videoanruf", de),
+            MessageType::Call {
+                video: true,
+                missed: true
+            }
+        );
+
+        let es = Locale::find("es").unwrap();
+        assert_eq!(
+            MessageType::detect("‎imagen omitida", es),
+            MessageType::Image
+        );
+        assert_eq!(
+            MessageType::detect("‎informe.pdf • ‎3 páginas ‎documento omitido", es),
+            MessageType::Document(String::from("informe.pdf"))
+        );
+        assert_eq!(MessageType::detect("‎image omitted", es), MessageType::Text);
+
+        let pt = Locale::find("pt").unwrap();
+        assert_eq!(
+            MessageType::detect("<Mídia oculta>", pt),
+            MessageType::Unknown
+        );
+    }
+
+    #[test]
+    fn detect_ignores_lookalike_text() {
+        assert_eq!(
+            MessageType::detect("I omitted the image from the deck", Locale::english()),
+            MessageType::Text
+        );
+        assert_eq!(
+            MessageType::detect("the video call was fun", Locale::english()),
+            MessageType::Text
+        );
+        assert_eq!(
+            MessageType::detect("image omitted, sorry", Locale::english()),
+            MessageType::Text
+        );
+        assert_eq!(
+            MessageType::detect("Location: Berlin, see you there", Locale::english()),
+            MessageType::Text
+        );
+    }
+
+    #[test]
+    fn from_str_identifies_attachments() {
+        let m = Message::from_str(
+            "2019-09-11, 01:57:17",
+            "Foo Bar",
+            "‎<attached: 00000012-PHOTO-2019-09-11-01-57-17.jpg>",
+        )
+        .unwrap();
+        assert_eq!(m.kind, MessageType::Image);
+        assert_eq!(
+            m.attachment.unwrap().name,
+            "00000012-PHOTO-2019-09-11-01-57-17.jpg"
+        );
+    }
+
+    #[test]
+    fn from_str_strips_edited_marker() {
+        let m = Message::from_str(
+            "2019-09-11, 01:57:17",
+            "Foo Bar",
+            "Baz Qux ‎<This message was edited>",
+        )
+        .unwrap();
+        assert_eq!(m.text, "Baz Qux");
+        assert!(m.edited);
+        assert_eq!(m.words(), vec!["Baz", "Qux"]);
+
+        let m = Message::from_str(
+            "2019-09-11, 01:57:17",
+            "Foo Bar",
+            "<This message was edited> is what it says",
+        )
+        .unwrap();
+        assert!(!m.edited);
+    }
+
+    #[test]
+    fn system_parses_event() {
+        let m = Message::system(
+            zone::utc(
+                &NaiveDate::from_ymd_opt(2019, 9, 11)
+                    .unwrap()
+                    .and_hms_opt(1, 57, 17)
+                    .unwrap(),
+            ),
+            "Group",
+            "\u{200e}Foo left",
+            Locale::english(),
+        );
+        assert_eq!(m.kind, MessageType::System);
+        assert_eq!(
+            m.event,
+            Some(ChatEvent::MemberLeft {
+                member: String::from("Foo")
+            })
+        );
+    }
+
     #[test]
     fn from_str_throw_error_on_invalid_date() {
         assert!(
@@ -162,4 +542,21 @@ mod tests {
         let m = Message::from_str("2019-09-11, 01:57:17", "Foo Bar", "Baz Qux").unwrap();
         assert_eq!(m.words(), vec!["Baz", "Qux"]);
     }
+
+    #[test]
+    fn normalize_works() {
+        assert_eq!(normalize("\u{200f}Foo\u{200e} "), "Foo");
+        assert_eq!(normalize("\u{2067}Cafe\u{301}\u{2069}"), "Caf\u{e9}");
+    }
+
+    #[test]
+    fn new_normalizes_author_and_text() {
+        let a = Message::from_str("2019-09-11, 01:57:17", "\u{200f}Foo\u{200e}", "Cafe\u{301}")
+            .unwrap();
+        let b = Message::from_str("2019-09-11, 01:57:17", "Foo", "\u{200e}image omitted").unwrap();
+
+        assert_eq!(a.author, "Foo");
+        assert_eq!(a.text, "Caf\u{e9}");
+        assert_eq!(b.kind, MessageType::Image);
+    }
 }
@@ -0,0 +1,134 @@
+use std::io::{self, BufRead, Write};
+
+use crate::message::{Message, MessageError, MessageErrorKind};
+use crate::stream::Diagnostic;
+
+// one message per line, as `export` writes them
+pub fn write<W: Write>(mut writer: W, message: &Message) -> io::Result<()> {
+    serde_json::to_writer(&mut writer, message)?;
+    writer.write_all(b"\n")
+}
+
+// messages read back from what `export` wrote, blank lines are skipped. a line that isn't a
+// message is reported like a line of a chat that can't be parsed
+pub struct Records<R> {
+    lines: io::Lines<R>,
+    line: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R) -> Records<R> {
+        Records {
+            lines: reader.lines(),
+            line: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = Result<Message, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let text = self.lines.next()?;
+            self.line += 1;
+
+            let text = match text {
+                Ok(text) => text,
+                Err(_) => {
+                    return Some(Err(Diagnostic {
+                        file: None,
+                        line: self.line,
+                        column: 1,
+                        text: String::new(),
+                        error: MessageError(MessageErrorKind::Unreadable),
+                    }))
+                }
+            };
+
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            return Some(serde_json::from_str(&text).map_err(|err| Diagnostic {
+                file: None,
+                line: self.line,
+                column: err.column().max(1),
+                text,
+                error: MessageError(MessageErrorKind::InvalidRecord),
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversation::Conversation;
+
+    static MOCK: &str = "
+[2019-09-11, 01:57:17] Foo: Hey! 💩 ‎<This message was edited>
+[2019-09-11, 01:58:00] Group: ‎Foo added Bar
+[2019-09-11, 13:01:00] Bar: ‎<attached: 00000012-PHOTO-2019-09-11-13-01-00.jpg>
+[2019-09-13, 22:15:00] Foo: ‎Missed video call
+[2019-09-13, 22:16:00] Bar: ‎report.pdf • 3 pages ‎document omitted
+[2019-09-13, 22:17:00] Foo: multi
+line
+";
+
+    fn export(c: Conversation) -> String {
+        let mut out = Vec::new();
+        for m in c.into_messages().iter() {
+            write(&mut out, m).unwrap();
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn write_works() {
+        let raw = export(Conversation::from_str(MOCK).unwrap());
+        let lines: Vec<&str> = raw.lines().collect();
+
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[0],
+            r#"{"datetime":"2019-09-11T01:57:17Z","author":"Foo","text":"Hey! 💩","kind":"Text","edited":true}"#
+        );
+        assert!(lines[1].contains(r#""kind":"System","event":{"MemberAdded""#));
+        assert!(lines[3].contains(r#""kind":{"Call":{"video":true,"missed":true}}"#));
+        assert!(lines[5].contains(r#""text":"multi\nline""#));
+    }
+
+    #[test]
+    fn records_rebuild_the_conversation() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let raw = format!("\n{}\n", export(c.clone()));
+        let rebuilt: Conversation = Records::new(raw.as_bytes())
+            .map(|m| m.map_err(|d| d.error))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(rebuilt.messages(), c.messages());
+        assert_eq!(rebuilt.events(), c.events());
+        assert_eq!(rebuilt.participants(), c.participants());
+    }
+
+    #[test]
+    fn records_reports_invalid_lines() {
+        let raw = r#"{"datetime":"2019-09-11T01:57:17+02:00","author":"Foo","text":"hi","kind":"Text"}
+
+{"datetime":"2019-09-11","author":"Foo","text":"hi","kind":"Text"}
+"#;
+        let records: Vec<_> = Records::new(raw.as_bytes()).collect();
+
+        let message = records[0].as_ref().unwrap();
+        assert_eq!(message.datetime.to_rfc3339(), "2019-09-11T01:57:17+02:00");
+        assert!(!message.edited);
+
+        let diagnostic = records[1].as_ref().unwrap_err();
+        assert_eq!(diagnostic.line, 3);
+        assert_eq!(diagnostic.error.to_string(), "invalid message record");
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt::Write;
+
+use chrono::Duration;
+
+use crate::conversation::{Timeline, TimelineType};
+use crate::emoji;
+use crate::message::Message;
+use crate::summary::Summary;
+
+// widest a bar of the hour chart gets
+const BAR: usize = 40;
+// emojis listed at the bottom
+const TOP_EMOJIS: usize = 10;
+// widest the timeline sparkline gets, longer timelines are squeezed into it
+const SPARKLINE: usize = 60;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII_SPARKS: [char; 8] = ['_', '.', '-', ':', '=', '+', '*', '#'];
+
+// how the report is drawn, terminals get colours and block characters, anything else plain
+// ascii
+#[derive(Clone, Copy, Debug)]
+pub struct Style {
+    pub color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn heading(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    fn bar(&self, width: usize) -> String {
+        if self.color {
+            self.paint("36", &"█".repeat(width))
+        } else {
+            "#".repeat(width)
+        }
+    }
+
+    fn sparks(&self) -> &'static [char; 8] {
+        if self.color {
+            &SPARKS
+        } else {
+            &ASCII_SPARKS
+        }
+    }
+}
+
+// `1d 02h 03m`
+fn duration(duration: &Duration) -> String {
+    format!(
+        "{}d {:02}h {:02}m",
+        duration.num_days(),
+        duration.num_hours() % 24,
+        duration.num_minutes() % 60
+    )
+}
+
+fn message(message: Option<&Message>) -> String {
+    match message {
+        Some(m) => format!(
+            "{} by {}",
+            m.datetime.format("%Y-%m-%d %H:%M:%S %:z"),
+            m.author
+        ),
+        None => String::from("-"),
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    format!("{}{}", text, " ".repeat(width.saturating_sub(len)))
+}
+
+fn share(part: usize, whole: usize) -> f32 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f32 * 100.0 / whole as f32
+    }
+}
+
+fn summary_block(out: &mut String, summary: &Summary, style: &Style) {
+    let (words, letters) = summary.average();
+    let rows = [
+        ("first message", message(summary.first())),
+        ("last message", message(summary.last())),
+        (
+            "duration",
+            summary.duration().map(|d| duration(&d)).unwrap_or_default(),
+        ),
+        ("messages", summary.count().to_string()),
+        ("words", summary.words().to_string()),
+        ("letters", summary.letters().to_string()),
+        ("participants", summary.participants().len().to_string()),
+        ("words per message", format!("{:.2}", words)),
+        ("letters per message", format!("{:.2}", letters)),
+    ];
+
+    writeln!(out, "{}", style.heading("Summary")).unwrap();
+    for (label, value) in rows.iter() {
+        writeln!(out, "  {} {}", pad(label, 20), value).unwrap();
+    }
+}
+
+// participants from the most to the least active one, as in the json
+fn leaderboard(out: &mut String, summary: &Summary, style: &Style) {
+    let participants = summary.leaderboard();
+
+    let width = participants
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+
+    writeln!(out, "\n{}", style.heading("Participants")).unwrap();
+    writeln!(
+        out,
+        "  {:>3}  {} {:>9} {:>7} {:>9} {:>9}",
+        "#",
+        pad("name", width),
+        "messages",
+        "share",
+        "words",
+        "letters"
+    )
+    .unwrap();
+
+    for (i, (name, stats)) in participants.iter().enumerate() {
+        writeln!(
+            out,
+            "  {:>3}  {} {:>9} {:>6.1}% {:>9} {:>9}",
+            i + 1,
+            pad(name, width),
+            stats.messages,
+            share(stats.messages, summary.count()),
+            stats.words,
+            stats.letters
+        )
+        .unwrap();
+    }
+}
+
+fn hours(out: &mut String, summary: &Summary, style: &Style) {
+    let frequency = summary.frequency();
+    let max = frequency.values().cloned().max().unwrap_or(0) as usize;
+
+    writeln!(out, "\n{}", style.heading("Hours")).unwrap();
+    for (hour, count) in frequency.iter() {
+        let count = *count as usize;
+        let width = (count * BAR).checked_div(max).unwrap_or(0);
+
+        writeln!(
+            out,
+            "  {} |{}{} {}",
+            hour,
+            style.bar(width),
+            " ".repeat(BAR - width),
+            count
+        )
+        .unwrap();
+    }
+}
+
+// one character per period, scaled between the quietest and the busiest one
+pub fn sparkline(values: &[usize], sparks: &[char; 8]) -> String {
+    let min = values.iter().cloned().min().unwrap_or(0);
+    let max = values.iter().cloned().max().unwrap_or(0);
+
+    values
+        .iter()
+        .map(|v| {
+            if max == min {
+                sparks[0]
+            } else {
+                sparks[(v - min) * (sparks.len() - 1) / (max - min)]
+            }
+        })
+        .collect()
+}
+
+// at most `width` values, each the busiest of a run of consecutive ones
+pub fn downsample(values: &[usize], width: usize) -> Vec<usize> {
+    if values.len() <= width {
+        return values.to_vec();
+    }
+
+    (0..width)
+        .map(|i| {
+            let run = &values[i * values.len() / width..(i + 1) * values.len() / width];
+            run.iter().cloned().max().unwrap_or(0)
+        })
+        .collect()
+}
+
+fn timeline(out: &mut String, timeline: &Timeline, kind: TimelineType, style: &Style) {
+    let series = timeline.messages();
+    let values: Vec<usize> = series.iter().map(|(_, count)| *count).collect();
+
+    writeln!(out, "\n{}", style.heading(&format!("Timeline ({})", kind))).unwrap();
+
+    if let (Some((first, _)), Some((last, _))) = (series.first(), series.last()) {
+        writeln!(
+            out,
+            "  {}",
+            style.paint(
+                "36",
+                &sparkline(&downsample(&values, SPARKLINE), style.sparks())
+            )
+        )
+        .unwrap();
+        writeln!(
+            out,
+            "  {} to {}, {} period{}, {} to {} messages",
+            first,
+            last,
+            series.len(),
+            if series.len() == 1 { "" } else { "s" },
+            values.iter().min().unwrap(),
+            values.iter().max().unwrap()
+        )
+        .unwrap();
+    }
+}
+
+fn emojis(out: &mut String, summary: &Summary, style: &Style) {
+    let emojis = summary.emojis();
+    let ranked = emoji::ranked(&emojis);
+
+    writeln!(out, "\n{}", style.heading("Top emojis")).unwrap();
+    if ranked.is_empty() {
+        writeln!(out, "  none").unwrap();
+    }
+
+    for (emoji, count) in ranked.into_iter().take(TOP_EMOJIS) {
+        writeln!(out, "  {} {}", emoji, count).unwrap();
+    }
+}
+
+// what `--pretty` prints instead of the json
+pub fn render(summary: &Summary, timeline_type: TimelineType, style: Style) -> String {
+    let mut out = String::new();
+
+    summary_block(&mut out, summary, &style);
+    leaderboard(&mut out, summary, &style);
+    hours(&mut out, summary, &style);
+    timeline(
+        &mut out,
+        &summary.timeline(timeline_type),
+        timeline_type,
+        &style,
+    );
+    emojis(&mut out, summary, &style);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversation::Conversation;
+
+    static MOCK: &str = r"
+[2019-09-11, 01:57:17] Foo: Hey! 💩
+[2019-09-11, 13:01:00] Bar Baz: heyyyyyyy, 'sup 💩💩
+[2019-09-13, 22:15:00] Foo: there
+[2019-10-01, 00:00:00] Bar Baz: 🙄
+[2019-12-24, 09:30:00] Foo: bye
+";
+
+    fn render_mock(color: bool) -> String {
+        let c = Conversation::from_str(MOCK).unwrap();
+        render(&Summary::from(&c), TimelineType::Monthly, Style { color })
+    }
+
+    #[test]
+    fn render_works() {
+        let report = render_mock(false);
+
+        assert!(report.contains("  messages             5\n"));
+        assert!(report.contains("  duration             104d 07h 32m\n"));
+        assert!(report.contains("    1  Foo             3   60.0%"));
+        assert!(report.contains("    2  Bar Baz         2   40.0%"));
+        assert!(report.contains(&format!("  01h |{} 1\n", "#".repeat(40))));
+        assert!(report.contains("  22h |#"));
+        assert!(report.contains("  2019-09-01T00:00:00 to 2019-12-01T00:00:00, 4 periods"));
+        assert!(report.contains("  💩 3\n  🙄 1\n"));
+    }
+
+    #[test]
+    fn render_is_plain_without_color() {
+        let report = render_mock(false);
+        let emojis = report.find("Top emojis").unwrap();
+
+        assert!(report[..emojis].is_ascii());
+        assert!(render_mock(true).contains("\x1b[1mSummary\x1b[0m"));
+        assert!(render_mock(true).contains('█'));
+    }
+
+    #[test]
+    fn render_counts_a_single_period() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let report = render(
+            &Summary::from(&c),
+            TimelineType::Yearly,
+            Style { color: false },
+        );
+
+        assert!(report.contains("  2019-01-01T00:00:00 to 2019-01-01T00:00:00, 1 period, "));
+    }
+
+    #[test]
+    fn render_squeezes_long_timelines() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let report = render(
+            &Summary::from(&c),
+            TimelineType::Hourly,
+            Style { color: false },
+        );
+        let mut lines = report.lines().skip_while(|l| !l.starts_with("Timeline"));
+        let line = lines.nth(1).unwrap();
+
+        assert_eq!(line.chars().count(), 2 + SPARKLINE);
+        assert!(report.contains(", 2505 periods, "));
+    }
+
+    #[test]
+    fn downsample_works() {
+        assert_eq!(downsample(&[1, 2, 3], 5), vec![1, 2, 3]);
+        assert_eq!(downsample(&[1, 5, 0, 0, 2, 1], 3), vec![5, 0, 2]);
+        assert_eq!(downsample(&[1, 5, 0, 0, 2], 2), vec![5, 2]);
+    }
+
+    #[test]
+    fn sparkline_works() {
+        assert_eq!(sparkline(&[0, 1, 7, 3], &SPARKS), "▁▂█▄");
+        assert_eq!(sparkline(&[2, 2], &ASCII_SPARKS), "__");
+        assert_eq!(sparkline(&[], &SPARKS), "");
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::prelude::*;
+use regex::{Captures, Regex};
+use serde::de::IgnoredAny;
+
+use crate::attachment::Attachment;
+use crate::conversation::Conversation;
+use crate::encoding;
+use crate::event::ChatEvent;
+use crate::message::{normalize, Message, MessageType};
+
+// a workspace export has `users.json` at its root and a directory per channel holding one
+// `2019-09-11.json` file per day
+#[derive(Debug, Deserialize)]
+struct User {
+    id: String,
+    name: String,
+    real_name: Option<String>,
+    #[serde(default)]
+    profile: Profile,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Profile {
+    display_name: Option<String>,
+    real_name: Option<String>,
+}
+
+impl Profile {
+    fn name(&self) -> Option<&str> {
+        self.display_name
+            .as_deref()
+            .filter(|v| !v.is_empty())
+            .or_else(|| self.real_name.as_deref().filter(|v| !v.is_empty()))
+    }
+}
+
+impl User {
+    fn display_name(&self) -> &str {
+        self.profile
+            .name()
+            .or_else(|| self.real_name.as_deref().filter(|v| !v.is_empty()))
+            .unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct File {
+    name: Option<String>,
+    size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    #[serde(default)]
+    subtype: String,
+    ts: String,
+    thread_ts: Option<String>,
+    user: Option<String>,
+    username: Option<String>,
+    user_profile: Option<Profile>,
+    #[serde(default)]
+    text: String,
+    edited: Option<IgnoredAny>,
+    #[serde(default)]
+    files: Vec<File>,
+    name: Option<String>,
+}
+
+// `1568166437.000200`, seconds and microseconds since the epoch in utc
+fn parse_ts(ts: &str) -> Option<DateTime<FixedOffset>> {
+    let (secs, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+
+    DateTime::from_timestamp(secs.parse().ok()?, micros.parse::<u32>().ok()? * 1000)
+        .map(|dt| dt.fixed_offset())
+}
+
+struct Users(HashMap<String, String>);
+
+impl Users {
+    fn name<'a>(&'a self, id: &'a str) -> &'a str {
+        self.0.get(id).map_or(id, |v| v.as_str())
+    }
+
+    fn author(&self, entry: &Entry) -> String {
+        match &entry.user {
+            Some(id) if self.0.contains_key(id) => self.name(id).to_string(),
+            id => normalize(
+                entry
+                    .user_profile
+                    .as_ref()
+                    .and_then(|p| p.name())
+                    .or(entry.username.as_deref())
+                    .or(id.as_deref())
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+
+    // `<@U024BE7LH>` mentions, `<#C024BE7LR|general>` channels and `<https://…|label>` links
+    // are written in slack's markup, the text keeps what the app shows instead
+    fn format(&self, text: &str) -> String {
+        lazy_static! {
+            static ref MARKUP: Regex =
+                Regex::new(r"<(?P<sigil>[@#!]?)(?P<target>[^>|]+)(?:\|(?P<label>[^>]+))?>")
+                    .expect("invalid regex");
+        }
+
+        let text = MARKUP.replace_all(text, |c: &Captures| {
+            let label = c.name("label").map(|m| m.as_str());
+
+            match &c["sigil"] {
+                "@" => format!("@{}", label.unwrap_or_else(|| self.name(&c["target"]))),
+                "!" => format!("@{}", label.unwrap_or(&c["target"])),
+                "#" => format!("#{}", label.unwrap_or(&c["target"])),
+                _ => label.unwrap_or(&c["target"]).to_string(),
+            }
+        });
+
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+    }
+}
+
+impl Entry {
+    fn event(&self, author: &str) -> Option<ChatEvent> {
+        let by = author.to_string();
+
+        match self.subtype.as_str() {
+            "channel_join" | "group_join" => Some(ChatEvent::MemberJoined { member: by }),
+            "channel_leave" | "group_leave" => Some(ChatEvent::MemberLeft { member: by }),
+            "channel_name" | "group_name" => Some(ChatEvent::SubjectChanged {
+                by,
+                subject: self.name.clone().unwrap_or_default(),
+            }),
+            "channel_topic" | "group_topic" | "channel_purpose" | "group_purpose" => {
+                Some(ChatEvent::DescriptionChanged { by })
+            }
+            _ => None,
+        }
+    }
+
+    fn is_system(&self) -> bool {
+        self.event("").is_some()
+            || matches!(
+                self.subtype.as_str(),
+                "channel_archive" | "channel_unarchive" | "pinned_item" | "unpinned_item"
+            )
+    }
+}
+
+pub fn parse(users: &str, days: &[String]) -> serde_json::Result<Conversation> {
+    let users: Vec<User> = serde_json::from_str(users)?;
+    let users = Users(
+        users
+            .iter()
+            .map(|u| (u.id.clone(), normalize(u.display_name())))
+            .collect(),
+    );
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for day in days {
+        entries.extend(serde_json::from_str::<Vec<Entry>>(day)?);
+    }
+
+    let mut messages = Vec::new();
+    let mut events = Vec::new();
+
+    for entry in entries {
+        let datetime = match parse_ts(&entry.ts) {
+            Some(datetime) => datetime,
+            None => continue,
+        };
+
+        let author = users.author(&entry);
+        let text = normalize(&users.format(&entry.text));
+
+        if entry.is_system() {
+            events.push(Message {
+                datetime,
+                event: entry.event(&author),
+                author,
+                text,
+                kind: MessageType::System,
+                attachment: None,
+                edited: false,
+                reply_to: None,
+            });
+            continue;
+        }
+
+        // files hidden by the plan limits of the workspace come without a name
+        let attachment = entry
+            .files
+            .iter()
+            .find_map(|f| f.name.as_ref().map(|name| (name, f.size)))
+            .map(|(name, size)| Attachment {
+                name: name.to_string(),
+                size,
+            });
+
+        let kind = match (&attachment, entry.subtype.as_str()) {
+            (_, "tombstone") => MessageType::Deleted,
+            (Some(attachment), _) => attachment.kind(),
+            (None, _) => MessageType::Text,
+        };
+
+        // the first message of a thread carries its own `ts` as `thread_ts`
+        let reply_to = entry
+            .thread_ts
+            .as_ref()
+            .filter(|ts| **ts != entry.ts)
+            .and_then(|ts| parse_ts(ts));
+
+        messages.push(Message {
+            datetime,
+            author,
+            text,
+            kind,
+            event: None,
+            attachment,
+            edited: entry.edited.is_some(),
+            reply_to,
+        });
+    }
+
+    // replies broadcast to the channel can show up out of order
+    messages.sort_by_key(|m| m.datetime);
+    events.sort_by_key(|m| m.datetime);
+
+    Ok(Conversation::from_messages(messages, events))
+}
+
+fn is_day(name: &str) -> bool {
+    lazy_static! {
+        static ref DAY: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2}\.json$").expect("invalid regex");
+    }
+
+    DAY.is_match(name)
+}
+
+// reads the directory of a single channel, `users.json` is looked up next to it or in the root
+// of the export
+pub fn read(dir: &Path) -> io::Result<Conversation> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| is_day(name))
+        .collect();
+    names.sort();
+
+    let days = names
+        .iter()
+        .map(|name| fs::read(dir.join(name)).and_then(encoding::decode_bytes))
+        .collect::<io::Result<Vec<String>>>()?;
+
+    let users = [Some(dir), dir.parent()]
+        .iter()
+        .flatten()
+        .map(|d| d.join("users.json"))
+        .find(|p| p.is_file());
+
+    let users = match users {
+        Some(path) => encoding::decode_bytes(fs::read(path)?)?,
+        None => String::from("[]"),
+    };
+
+    Ok(parse(&users, &days)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static USERS: &str = r#"[
+  {"id": "U01", "name": "foo", "real_name": "Foo Bar", "profile": {"display_name": "Foo", "real_name": "Foo Bar"}},
+  {"id": "U02", "name": "baz", "profile": {"display_name": "", "real_name": "Baz Qux"}}
+]"#;
+
+    static FIRST_DAY: &str = r#"[
+  {"type": "message", "subtype": "channel_join", "user": "U02", "text": "<@U02> has joined the channel", "ts": "1568166437.000200"},
+  {"type": "message", "user": "U01", "text": "hey <@U02>, see <https://example.com|this> &amp; <#C01|general>", "ts": "1568166500.000100", "thread_ts": "1568166500.000100", "reply_count": 1},
+  {"type": "message", "user": "U02", "text": "nice", "ts": "1568166600.000300", "thread_ts": "1568166500.000100", "parent_user_id": "U01", "edited": {"user": "U02", "ts": "1568166700.000000"}}
+]"#;
+
+    static SECOND_DAY: &str = r#"[
+  {"type": "message", "subtype": "file_share", "user": "U01", "text": "", "ts": "1568252837.000200", "files": [{"id": "F01", "name": "plan.pdf", "mimetype": "application/pdf", "size": 42}]},
+  {"type": "message", "subtype": "bot_message", "username": "deploybot", "bot_id": "B01", "text": "deployed", "ts": "1568252900.000000"}
+]"#;
+
+    fn mock() -> Conversation {
+        parse(USERS, &[FIRST_DAY.to_string(), SECOND_DAY.to_string()]).unwrap()
+    }
+
+    #[test]
+    fn parse_works() {
+        let c = mock();
+
+        assert_eq!(c.count(), 4);
+        assert_eq!(c.participants(), &vec!["Foo", "Baz Qux", "deploybot"]);
+        assert_eq!(
+            format!("{}", c.first().unwrap().datetime.naive_local()),
+            "2019-09-11 01:48:20.000100"
+        );
+        assert_eq!(c.first().unwrap().text, "hey @Baz Qux, see this & #general");
+    }
+
+    #[test]
+    fn parse_maps_threads_and_files() {
+        let c = mock();
+
+        let reply = c
+            .by_author(String::from("Baz Qux"))
+            .first()
+            .cloned()
+            .unwrap();
+        assert_eq!(reply.reply_to, Some(c.first().unwrap().datetime));
+        assert!(reply.edited);
+        assert_eq!(c.first().unwrap().reply_to, None);
+
+        let file = c.by_author(String::from("Foo")).last().cloned().unwrap();
+        assert_eq!(file.kind, MessageType::Document(String::from("plan.pdf")));
+        assert_eq!(file.attachment.unwrap().size, Some(42));
+    }
+
+    #[test]
+    fn parse_maps_channel_events() {
+        let c = mock();
+
+        assert_eq!(c.events().len(), 1);
+        assert_eq!(
+            c.events()[0].event,
+            Some(ChatEvent::MemberJoined {
+                member: String::from("Baz Qux"),
+            })
+        );
+    }
+
+    #[test]
+    fn is_day_works() {
+        assert!(is_day("2019-09-11.json"));
+        assert!(!is_day("users.json"));
+        assert!(!is_day("channels.json"));
+    }
+}
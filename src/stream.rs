@@ -0,0 +1,523 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, Lines};
+use std::iter::Peekable;
+
+use chrono::{DateTime, FixedOffset};
+use chrono_tz::Tz;
+use serde::Serializer;
+
+use crate::conversation::ParseOptions;
+use crate::dialect::Dialect;
+use crate::event::ChatEvent;
+use crate::format::{self, Format};
+use crate::locale::{self, Locale};
+use crate::message::{Message, MessageError, MessageErrorKind, Result};
+use crate::zone;
+
+// entries read ahead to detect the dialect and the language of an export start with what the
+// language is detected from, more are read as long as the timestamps stay ambiguous, up to this
+const MAX_READ_AHEAD: usize = 8 * locale::SAMPLE_SIZE;
+
+// a line that couldn't be read as part of a message, the file is filled in by whoever knows it
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    #[serde(rename = "reason", serialize_with = "reason")]
+    pub error: MessageError,
+}
+
+fn reason<S: Serializer>(
+    error: &MessageError,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(error)
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}, ", file)?;
+        }
+
+        write!(
+            f,
+            "line {}, column {}: {} in {:?}",
+            self.line, self.column, self.error, self.text
+        )
+    }
+}
+
+// raw parts of a message before its timestamp is parsed, system lines come without an author.
+// the line it starts on is kept around to report it
+struct Entry {
+    datetime: String,
+    author: Option<String>,
+    text: String,
+    line: usize,
+    column: usize,
+    raw: String,
+}
+
+impl Entry {
+    // ios marks system lines with a leading U+200E and keeps the group name as their author,
+    // android drops the author altogether
+    fn is_system_shaped(&self) -> bool {
+        self.author.is_none() || self.text.starts_with('\u{200e}')
+    }
+
+    // the encryption notice is a sentence of its own that no message reads like, it's taken for
+    // a system line even without the U+200E older ios exports leave out
+    fn is_system(&self, locale: &Locale) -> bool {
+        match self.author {
+            None => true,
+            Some(_) => match ChatEvent::parse(&self.text, locale) {
+                Some(ChatEvent::Encrypted) => true,
+                Some(_) => self.is_system_shaped(),
+                None => false,
+            },
+        }
+    }
+
+    // the author pattern can still split an android system line like
+    // `Foo changed the subject from "a: b" to "c"`, puts it back together
+    fn rejoin(&mut self, locale: &Locale) {
+        if let Some(author) = &self.author {
+            let line = format!("{}: {}", author, self.text);
+
+            if ChatEvent::parse(&line, locale).is_some() {
+                self.author = None;
+                self.text = line;
+            }
+        }
+    }
+
+    fn diagnostic(self, error: MessageError) -> Diagnostic {
+        Diagnostic {
+            file: None,
+            line: self.line,
+            column: self.column,
+            text: self.raw,
+            error,
+        }
+    }
+}
+
+// groups lines into entries, lines without a header belong to the entry before them
+struct Entries<R> {
+    head: VecDeque<String>,
+    lines: Lines<R>,
+    format: Format,
+    current: Option<Entry>,
+    // number of lines read so far
+    count: usize,
+}
+
+impl<R: BufRead> Entries<R> {
+    fn next_line(&mut self) -> Option<std::result::Result<String, Diagnostic>> {
+        self.count += 1;
+
+        match self.head.pop_front() {
+            Some(line) => Some(Ok(line)),
+            None => self.lines.next().map(|line| {
+                line.map_err(|_| Diagnostic {
+                    file: None,
+                    line: self.count,
+                    column: 1,
+                    text: String::new(),
+                    error: MessageError(MessageErrorKind::Unreadable),
+                })
+            }),
+        }
+    }
+
+    fn fill(&mut self, ahead: &mut VecDeque<std::result::Result<Entry, Diagnostic>>, limit: usize) {
+        while ahead.len() < limit {
+            match self.next() {
+                Some(entry) => ahead.push_back(entry),
+                None => break,
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Entries<R> {
+    type Item = std::result::Result<Entry, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.next_line() {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return self.current.take().map(Ok),
+            };
+
+            match self.format.pattern().captures(&line) {
+                Some(capture) => {
+                    let datetime = capture.name("datetime").expect("pattern without datetime");
+                    let entry = Entry {
+                        datetime: datetime.as_str().to_string(),
+                        author: capture.name("author").map(|m| m.as_str().to_string()),
+                        text: capture["text"].to_string(),
+                        line: self.count,
+                        column: line[..datetime.start()].chars().count() + 1,
+                        raw: line.clone(),
+                    };
+
+                    if let Some(previous) = self.current.replace(entry) {
+                        return Some(Ok(previous));
+                    }
+                }
+                None => {
+                    if let Some(entry) = self.current.as_mut() {
+                        entry.text.push('\n');
+                        entry.text.push_str(&line);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// reads the messages of an export one at a time, system messages are yielded along with the
+// others. layout, dialect and language are detected from the leading part of the export, lines
+// that can't be read are reported along with their position and the ones after them still are
+pub struct Messages<R> {
+    entries: Entries<R>,
+    ahead: VecDeque<std::result::Result<Entry, Diagnostic>>,
+    format: Format,
+    dialect: Dialect,
+    locale: &'static Locale,
+    // the zone of the phone that made the export and the time of the last message read
+    zone: Tz,
+    previous: Option<DateTime<FixedOffset>>,
+}
+
+impl<R: BufRead> Messages<R> {
+    pub fn new(reader: R, options: &ParseOptions) -> Result<Messages<R>> {
+        let mut lines = reader.lines();
+
+        let mut head = VecDeque::new();
+        for line in lines.by_ref().take(format::SAMPLE_SIZE) {
+            head.push_back(line.map_err(|_| MessageError(MessageErrorKind::Unreadable))?);
+        }
+
+        let format = match options.format {
+            Some(format) => format,
+            None => Format::detect(&Vec::from(head.clone()).join("\n")),
+        };
+
+        let mut entries = Entries {
+            head,
+            lines,
+            format,
+            current: None,
+            count: 0,
+        };
+
+        let mut ahead = VecDeque::new();
+        let mut limit = locale::SAMPLE_SIZE;
+        let builtin = Dialect::builtin();
+
+        let dialect = match &options.dialect {
+            Some(dialect) => dialect.clone(),
+            None => loop {
+                entries.fill(&mut ahead, limit);
+
+                // timestamps none of the dialects can read are left to be reported on their own
+                // line, repeats of the one before tell nothing new
+                let mut datetimes: Vec<&str> = ahead
+                    .iter()
+                    .flatten()
+                    .map(|e| e.datetime.as_str())
+                    .collect();
+                datetimes.dedup();
+                datetimes.retain(|v| builtin.iter().any(|d| d.parse(v).is_ok()));
+                match Dialect::detect(&datetimes) {
+                    Ok(dialect) => break dialect,
+                    Err(MessageError(MessageErrorKind::AmbiguousDate))
+                        if ahead.len() == limit && limit < MAX_READ_AHEAD =>
+                    {
+                        limit *= 2
+                    }
+                    Err(err) => return Err(err),
+                }
+            },
+        };
+
+        let locale = match options.locale {
+            Some(locale) => locale,
+            None => {
+                entries.fill(&mut ahead, locale::SAMPLE_SIZE);
+
+                let texts: Vec<(&str, bool)> = ahead
+                    .iter()
+                    .flatten()
+                    .map(|e| (e.text.as_str(), e.is_system_shaped()))
+                    .collect();
+                Locale::detect(&texts)
+            }
+        };
+
+        Ok(Messages {
+            entries,
+            ahead,
+            format,
+            dialect,
+            locale,
+            zone: options.zone.unwrap_or(Tz::UTC),
+            previous: None,
+        })
+    }
+
+    fn message(&mut self, mut entry: Entry) -> std::result::Result<Message, Diagnostic> {
+        if self.format == Format::Android {
+            entry.rejoin(self.locale);
+        }
+
+        let datetime = match self.dialect.parse(&entry.datetime) {
+            Ok(datetime) => zone::localize(&datetime, &self.zone, self.previous.as_ref()),
+            Err(err) => return Err(entry.diagnostic(err)),
+        };
+        self.previous = Some(datetime);
+
+        match &entry.author {
+            Some(author) if !entry.is_system(self.locale) => Ok(Message::new(
+                datetime,
+                author,
+                entry.text.trim(),
+                self.locale,
+            )),
+            _ => Ok(Message::system(
+                datetime,
+                entry.author.as_deref().unwrap_or_default(),
+                &entry.text,
+                self.locale,
+            )),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Messages<R> {
+    type Item = std::result::Result<Message, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = match self.ahead.pop_front().or_else(|| self.entries.next())? {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(err)),
+        };
+
+        Some(self.message(entry))
+    }
+}
+
+// merges streams that are each in chronological order into a single one, messages with the same
+// timestamp keep the order of their streams and errors are passed on as soon as they show up
+pub struct Merge<I: Iterator> {
+    streams: Vec<Peekable<I>>,
+}
+
+impl<I: Iterator> Merge<I> {
+    pub fn new(streams: Vec<I>) -> Merge<I> {
+        Merge {
+            streams: streams.into_iter().map(|s| s.peekable()).collect(),
+        }
+    }
+}
+
+impl<I, E> Iterator for Merge<I>
+where
+    I: Iterator<Item = std::result::Result<Message, E>>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut earliest = None;
+
+        for (i, stream) in self.streams.iter_mut().enumerate() {
+            match stream.peek() {
+                Some(Ok(message))
+                    if earliest.is_none_or(|(_, datetime)| message.datetime < datetime) =>
+                {
+                    earliest = Some((i, message.datetime));
+                }
+                Some(Err(_)) => return stream.next(),
+                _ => {}
+            }
+        }
+
+        let (i, _) = earliest?;
+        self.streams[i].next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::message::MessageType;
+
+    #[test]
+    fn messages_works() {
+        let raw = "[2019-09-11, 01:57:17] Foo: Hey\nthere\n[2019-09-11, 01:58:17] Bar: ok\n";
+        let messages: Vec<Message> = Messages::new(raw.as_bytes(), &ParseOptions::default())
+            .unwrap()
+            .collect::<std::result::Result<_, Diagnostic>>()
+            .unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].text, "Hey\nthere");
+        assert_eq!(messages[1].author, "Bar");
+    }
+
+    #[test]
+    fn messages_yields_system_messages() {
+        let raw = "12/31/19, 10:15 PM - Foo added Bar\n12/31/19, 10:16 PM - Bar: hi\n";
+        let kinds: Vec<MessageType> = Messages::new(raw.as_bytes(), &ParseOptions::default())
+            .unwrap()
+            .map(|m| m.unwrap().kind)
+            .collect();
+
+        assert_eq!(kinds, vec![MessageType::System, MessageType::Text]);
+    }
+
+    #[test]
+    fn messages_yields_encryption_notice_as_system_message() {
+        let raw = "[2019-09-11, 01:57:17] Group: Messages to this group are now secured with end-to-end encryption. Tap for more info.\n[2019-09-11, 01:58:17] Foo: hi\n";
+        let messages: Vec<Message> = Messages::new(raw.as_bytes(), &ParseOptions::default())
+            .unwrap()
+            .map(|m| m.unwrap())
+            .collect();
+
+        assert_eq!(messages[0].kind, MessageType::System);
+        assert_eq!(messages[0].event, Some(ChatEvent::Encrypted));
+        assert_eq!(messages[1].kind, MessageType::Text);
+    }
+
+    #[test]
+    fn messages_reads_ahead_past_ambiguous_dates() {
+        // every day of the first months reads fine either way, the 13th settles it
+        let mut raw = String::new();
+        for day in 1..=12 {
+            for month in 1..=12 {
+                raw.push_str(&format!("{:02}/{:02}/2019, 10:00 - Foo: hi\n", month, day));
+            }
+        }
+        raw.push_str("13/12/2019, 10:00 - Foo: hi\n");
+
+        let dialect = Messages::new(raw.as_bytes(), &ParseOptions::default())
+            .unwrap()
+            .dialect;
+        assert_eq!(dialect, Dialect::DayMonthYear);
+    }
+
+    #[test]
+    fn messages_gives_up_on_ambiguous_dates() {
+        let mut raw = "01/02/2019, 10:00 - Foo: hi\n".repeat(MAX_READ_AHEAD);
+        raw.push_str("13/02/2019, 10:00 - Foo: hi\n");
+
+        let err = Messages::new(raw.as_bytes(), &ParseOptions::default())
+            .err()
+            .unwrap();
+        assert!(matches!(err.0, MessageErrorKind::AmbiguousDate));
+    }
+
+    #[test]
+    fn messages_reads_nothing_ahead_when_told_dialect_and_locale() {
+        let raw = "01/02/2019, 10:00 - Foo: hi\n01/02/2019, 10:01 - Foo: there\n";
+        let options = ParseOptions {
+            dialect: Some(Dialect::DayMonthYear),
+            locale: Locale::find("en"),
+            ..ParseOptions::default()
+        };
+
+        let messages = Messages::new(raw.as_bytes(), &options).unwrap();
+        assert!(messages.ahead.is_empty());
+        assert_eq!(messages.count(), 2);
+    }
+
+    #[test]
+    fn merge_works() {
+        let first = "[2019-09-13, 01:57:17] Foo: one\n[2019-09-13, 01:59:17] Foo: three\n";
+        let second = "9/13/19, 1:58 AM - Bar: two\n9/13/19, 1:59 AM - Bar: four\n";
+
+        let streams = vec![
+            Messages::new(first.as_bytes(), &ParseOptions::default()).unwrap(),
+            Messages::new(second.as_bytes(), &ParseOptions::default()).unwrap(),
+        ];
+        let texts: Vec<String> = Merge::new(streams).map(|m| m.unwrap().text).collect();
+
+        assert_eq!(texts, vec!["one", "two", "four", "three"]);
+    }
+
+    #[test]
+    fn merge_passes_on_errors() {
+        let streams: Vec<std::vec::IntoIter<std::result::Result<Message, &str>>> = vec![
+            vec![Ok(
+                Message::from_str("2019-09-11, 01:57:17", "Foo", "one").unwrap()
+            )]
+            .into_iter(),
+            vec![Err("boom")].into_iter(),
+        ];
+        let mut merged = Merge::new(streams);
+
+        assert_eq!(merged.next().unwrap().unwrap_err(), "boom");
+        assert_eq!(merged.next().unwrap().unwrap().text, "one");
+        assert!(merged.next().is_none());
+    }
+
+    #[test]
+    fn messages_reports_invalid_dates() {
+        let raw = "[2019-09-11, 01:57:17] Foo: Hey\n";
+        let options = ParseOptions {
+            dialect: Some(Dialect::DayMonthYear),
+            ..ParseOptions::default()
+        };
+
+        let mut messages = Messages::new(raw.as_bytes(), &options).unwrap();
+        assert!(messages.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn messages_reports_position_of_invalid_lines() {
+        let raw = "[2019-09-11, 01:57:17] Foo: Hey\nthere\n\u{200e}[2019-13-45, 01:58:17] Bar: hi\n[2019-09-11, 01:59:17] Bar: ok\n";
+        let results: Vec<_> = Messages::new(raw.as_bytes(), &ParseOptions::default())
+            .unwrap()
+            .collect();
+
+        let diagnostic = results[1].as_ref().unwrap_err();
+        assert_eq!((diagnostic.line, diagnostic.column), (3, 3));
+        assert_eq!(diagnostic.text, "\u{200e}[2019-13-45, 01:58:17] Bar: hi");
+        assert!(matches!(diagnostic.error.0, MessageErrorKind::InvalidDate));
+        assert_eq!(results[2].as_ref().unwrap().text, "ok");
+    }
+
+    #[test]
+    fn messages_reads_timestamps_in_zone() {
+        // clocks in berlin fall back at 03:00, the hour after 02:00 happens twice
+        let raw = "[2019-10-27, 02:30:00] Foo: one\n[2019-10-27, 02:10:00] Foo: two\n[2019-10-27, 03:10:00] Foo: three\n";
+        let options = ParseOptions {
+            dialect: Some(Dialect::YearMonthDay),
+            zone: crate::zone::find("Europe/Berlin"),
+            ..ParseOptions::default()
+        };
+
+        let datetimes: Vec<String> = Messages::new(raw.as_bytes(), &options)
+            .unwrap()
+            .map(|m| m.unwrap().datetime.to_rfc3339())
+            .collect();
+
+        assert_eq!(
+            datetimes,
+            vec![
+                "2019-10-27T02:30:00+02:00",
+                "2019-10-27T02:10:00+01:00",
+                "2019-10-27T03:10:00+01:00"
+            ]
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Sub;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use crate::conversation::{
+    Conversation, Edits, Frequency, Media, Stats, Timeline, TimelineStats, TimelineType,
+};
+use crate::emoji::{self, Emojis};
+use crate::message::{Message, MessageError, MessageErrorKind, MessageType, Result};
+
+// amount of text collected before it gets scanned for emojis
+#[cfg(not(test))]
+const EMOJI_CHUNK: usize = 1 << 20;
+#[cfg(test)]
+const EMOJI_CHUNK: usize = 64;
+
+fn hours() -> Frequency {
+    (0..24).map(|n| (format!("{:02}h", n), 0)).collect()
+}
+
+#[derive(Debug, Default)]
+struct Hour {
+    total: Stats<usize>,
+    participants: HashMap<String, Stats<usize>>,
+}
+
+// works out what a `Conversation` reports in a single pass over its messages, only the first
+// and the last message, system messages and per hour totals are kept around
+#[derive(Debug)]
+pub struct Summary {
+    first: Option<Message>,
+    last: Option<Message>,
+    total: Stats<usize>,
+    // words and letters as counted per message for the averages
+    message_words: usize,
+    message_letters: usize,
+    participants: Vec<String>,
+    frequency: Frequency,
+    frequency_per_participant: HashMap<String, Frequency>,
+    hours: HashMap<NaiveDateTime, Hour>,
+    emojis: Emojis,
+    pending: String,
+    media: Media,
+    edits: Edits,
+    events: Vec<Message>,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Summary {
+            first: None,
+            last: None,
+            total: Stats::default(),
+            message_words: 0,
+            message_letters: 0,
+            participants: Vec::new(),
+            frequency: hours(),
+            frequency_per_participant: HashMap::new(),
+            hours: HashMap::new(),
+            emojis: HashMap::new(),
+            pending: String::new(),
+            media: Media::default(),
+            edits: Edits::default(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl From<&Conversation> for Summary {
+    fn from(cnv: &Conversation) -> Self {
+        let mut summary = Summary::default();
+
+        for message in cnv.messages().iter().chain(cnv.events().iter()) {
+            summary.add(message.clone());
+        }
+
+        summary
+    }
+}
+
+impl Summary {
+    pub fn add(&mut self, message: Message) {
+        if message.kind == MessageType::System {
+            self.events.push(message);
+            return;
+        }
+
+        if !self.participants.contains(&message.author) {
+            self.participants.push(message.author.clone());
+        }
+
+        self.total.add(&message);
+        self.message_words += message.words().len();
+        self.message_letters += message.letters().len();
+
+        let hour = format!("{:02}h", message.datetime.hour());
+        *self.frequency.entry(hour.clone()).or_default() += 1;
+        *self
+            .frequency_per_participant
+            .entry(message.author.clone())
+            .or_insert_with(hours)
+            .entry(hour)
+            .or_default() += 1;
+
+        let local = message.datetime.naive_local();
+        let start = local.date().and_hms_opt(local.hour(), 0, 0).unwrap();
+        let hour = self.hours.entry(start).or_default();
+        hour.total.add(&message);
+        hour.participants
+            .entry(message.author.clone())
+            .or_default()
+            .add(&message);
+
+        // none of the emojis are plain ascii, the separator keeps them from spanning messages
+        if !message.text.is_ascii() {
+            self.pending.push_str(&message.text);
+            self.pending.push('\n');
+
+            if self.pending.len() >= EMOJI_CHUNK {
+                let pending = std::mem::take(&mut self.pending);
+                merge(&mut self.emojis, emoji::count(&pending));
+            }
+        }
+
+        self.media.add(&message);
+        self.edits.add(&message);
+
+        if self.first.is_none() {
+            self.first = Some(message.clone());
+        }
+        self.last = Some(message);
+    }
+
+    pub fn first(&self) -> Option<&Message> {
+        self.first.as_ref()
+    }
+
+    pub fn last(&self) -> Option<&Message> {
+        self.last.as_ref()
+    }
+
+    pub fn duration(&self) -> Result<Duration> {
+        match (self.first(), self.last()) {
+            (Some(first), Some(last)) => Ok(last.datetime.sub(first.datetime)),
+            _ => Err(MessageError(MessageErrorKind::EmptyMessage)),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.total.messages
+    }
+
+    pub fn words(&self) -> usize {
+        self.total.words
+    }
+
+    pub fn letters(&self) -> usize {
+        self.total.letters
+    }
+
+    // average words and letters per message
+    pub fn average(&self) -> (f32, f32) {
+        let c = self.count() as f32;
+
+        (
+            self.message_words as f32 / c,
+            self.message_letters as f32 / c,
+        )
+    }
+
+    pub fn participants(&self) -> &Vec<String> {
+        &self.participants
+    }
+
+    pub fn events(&self) -> &Vec<Message> {
+        &self.events
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        self.frequency.clone()
+    }
+
+    pub fn frequency_per_participant(&self) -> HashMap<String, Frequency> {
+        self.frequency_per_participant.clone()
+    }
+
+    // participants from the most to the least active one, ties by name
+    pub fn leaderboard(&self) -> Vec<(String, Stats<usize>)> {
+        let mut participants: Vec<_> = self.stats_per_participant().into_iter().collect();
+        participants.sort_by_key(|(name, stats)| (Reverse(stats.messages), name.clone()));
+
+        participants
+    }
+
+    fn stats_per_participant(&self) -> HashMap<String, Stats<usize>> {
+        let mut participants: HashMap<String, Stats<usize>> = HashMap::new();
+
+        for hour in self.hours.values() {
+            for (p, stats) in hour.participants.iter() {
+                participants.entry(p.to_string()).or_default().merge(stats);
+            }
+        }
+
+        participants
+    }
+
+    pub fn emojis(&self) -> Emojis {
+        let mut emojis = self.emojis.clone();
+        merge(&mut emojis, emoji::count(&self.pending));
+
+        emojis
+    }
+
+    pub fn media(&self) -> Media {
+        self.media.clone()
+    }
+
+    pub fn edits(&self) -> Edits {
+        self.edits.clone()
+    }
+
+    // periods start on the hour, so they're made up of whole hours. only the hours messages
+    // were sent in are looked at, periods can be far longer than the conversation
+    pub fn timeline(&self, kind: TimelineType) -> Timeline {
+        let (first, last) = match (self.first(), self.last()) {
+            (Some(first), Some(last)) => {
+                (first.datetime.naive_local(), last.datetime.naive_local())
+            }
+            _ => return Timeline::from(BTreeMap::new()),
+        };
+
+        let hours: BTreeMap<NaiveDateTime, &Hour> =
+            self.hours.iter().map(|(k, v)| (*k, v)).collect();
+        let mut map = BTreeMap::new();
+
+        for period in kind.periods(&first, &last) {
+            let mut total = Stats::default();
+            let mut participants: HashMap<String, Stats<usize>> = self
+                .participants
+                .iter()
+                .map(|p| (p.to_string(), Stats::default()))
+                .collect();
+
+            for (_, hour) in hours.range(period.start..period.end) {
+                total.merge(&hour.total);
+
+                for (p, stats) in hour.participants.iter() {
+                    participants.entry(p.to_string()).or_default().merge(stats);
+                }
+            }
+
+            map.insert(
+                period.start,
+                TimelineStats::new(total, participants, kind, period.days),
+            );
+        }
+
+        Timeline::from(map)
+    }
+}
+
+fn merge(emojis: &mut Emojis, other: Emojis) {
+    for (emoji, count) in other {
+        *emojis.entry(emoji).or_default() += count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::Value;
+
+    use crate::conversation::Week;
+
+    static MOCK: &str = r"
+[2019-09-11, 01:57:17] Foo: Hey! 💩
+[2019-09-11, 13:01:00] Bar Baz: heyyyyyyy, 'sup 💩💩
+[2019-09-13, 22:15:00] Foo: ‎image omitted
+[2019-09-29, 23:59:59] Foo: there ‎<This message was edited>
+[2019-10-01, 00:00:00] Bar Baz: 🙄
+[2019-12-24, 09:30:00] Bar Baz: ‎Foo changed this group's icon
+[2020-01-02, 10:00:00] Foo: bye
+";
+
+    fn json<T: serde::Serialize>(value: T) -> Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn summary_matches_conversation() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let s = Summary::from(&c);
+
+        assert_eq!(s.first(), c.first());
+        assert_eq!(s.last(), c.last());
+        assert_eq!(s.duration().unwrap(), c.duration().unwrap());
+        assert_eq!(s.count(), c.count());
+        assert_eq!(s.words(), c.words());
+        assert_eq!(s.letters(), c.letters());
+        assert_eq!(s.average(), c.average());
+        assert_eq!(s.participants(), c.participants());
+        assert_eq!(s.events(), c.events());
+        assert_eq!(s.frequency(), c.frequency());
+        assert_eq!(
+            s.frequency_per_participant()["Foo"],
+            c.by_author(String::from("Foo")).frequency()
+        );
+        assert_eq!(s.emojis(), c.emojis());
+        assert_eq!(json(s.media()), json(c.media()));
+        assert_eq!(json(s.edits()), json(c.edits()));
+    }
+
+    #[test]
+    fn timeline_matches_conversation() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let s = Summary::from(&c);
+
+        for kind in [
+            TimelineType::Hourly,
+            TimelineType::Daily,
+            TimelineType::Weekly(Week::default()),
+            TimelineType::Weekly(Week {
+                start: Weekday::Sat,
+                iso: true,
+            }),
+            TimelineType::Monthly,
+            TimelineType::Quarterly,
+            TimelineType::Yearly,
+            TimelineType::from_name("6h", None).unwrap(),
+            TimelineType::from_name("2w", "2019-09-10T05:00:00".parse().ok()).unwrap(),
+            TimelineType::from_name("3000000000h", None).unwrap(),
+        ]
+        .iter()
+        {
+            assert_eq!(json(s.timeline(*kind)), json(c.timeline(*kind)), "{}", kind);
+        }
+    }
+
+    #[test]
+    fn timeline_is_serialized_in_order() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let s = Summary::from(&c);
+
+        let json = serde_json::to_string(&s.timeline(TimelineType::Daily)).unwrap();
+        let keys: Vec<&str> = json
+            .match_indices(r#"T00:00:00":{"#)
+            .map(|(i, _)| &json[i - 10..i])
+            .collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+
+        assert_eq!(keys.len(), 114);
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn emojis_are_counted_across_chunks() {
+        let mut s = Summary::default();
+        let text = format!("{} 💩", "a".repeat(EMOJI_CHUNK));
+
+        for _ in 0..3 {
+            s.add(Message::from_str("2019-09-11, 01:57:17", "Foo", &text).unwrap());
+        }
+
+        assert!(s.pending.len() < EMOJI_CHUNK);
+        assert_eq!(s.emojis()["💩"], 3);
+    }
+}
@@ -0,0 +1,231 @@
+use std::cmp::Reverse;
+use std::io;
+
+use csv::WriterBuilder;
+
+use crate::conversation::{Timeline, TimelineType};
+use crate::emoji;
+use crate::summary::Summary;
+
+// tables `--table` takes, in the order they're written
+pub const NAMES: [&str; 4] = ["timeline", "frequency", "participants", "emojis"];
+
+// one row per observation, ready for a spreadsheet. rows with an empty participant stand for
+// the whole chat
+#[derive(Debug)]
+pub struct Table {
+    pub name: &'static str,
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn write<W: io::Write>(&self, writer: W, delimiter: u8) -> csv::Result<()> {
+        let mut writer = WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(writer);
+
+        writer.write_record(self.header)?;
+        for row in self.rows.iter() {
+            writer.write_record(row)?;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn timeline(timeline: &Timeline) -> Table {
+    let mut rows = Vec::new();
+
+    for (label, stats) in timeline.iter() {
+        let mut participants: Vec<_> = stats.participants.iter().collect();
+        participants.sort_by_key(|(name, p)| (Reverse(p.total.messages), *name));
+
+        let whole = (String::new(), &stats.total, &stats.average);
+        let each = participants
+            .into_iter()
+            .map(|(name, p)| (name.clone(), &p.total, &p.average));
+
+        for (participant, total, average) in Some(whole).into_iter().chain(each) {
+            rows.push(vec![
+                label.clone(),
+                participant,
+                stats.days.to_string(),
+                total.messages.to_string(),
+                total.words.to_string(),
+                total.letters.to_string(),
+                average.messages.to_string(),
+                average.words.to_string(),
+                average.letters.to_string(),
+            ]);
+        }
+    }
+
+    Table {
+        name: "timeline",
+        header: &[
+            "bucket",
+            "participant",
+            "days",
+            "messages",
+            "words",
+            "letters",
+            "messages_per_day",
+            "words_per_day",
+            "letters_per_day",
+        ],
+        rows,
+    }
+}
+
+fn frequency(summary: &Summary) -> Table {
+    let per_participant = summary.frequency_per_participant();
+    let mut rows = Vec::new();
+
+    for (hour, count) in summary.frequency() {
+        rows.push(vec![hour.clone(), String::new(), count.to_string()]);
+
+        for (name, _) in summary.leaderboard() {
+            let count = per_participant[&name][&hour];
+            rows.push(vec![hour.clone(), name, count.to_string()]);
+        }
+    }
+
+    Table {
+        name: "frequency",
+        header: &["hour", "participant", "messages"],
+        rows,
+    }
+}
+
+fn participants(summary: &Summary) -> Table {
+    let total = summary.count() as f32;
+    let rows = summary
+        .leaderboard()
+        .into_iter()
+        .map(|(name, stats)| {
+            let messages = stats.messages as f32;
+
+            vec![
+                name,
+                stats.messages.to_string(),
+                (messages / total).to_string(),
+                stats.words.to_string(),
+                stats.letters.to_string(),
+                (stats.words as f32 / messages).to_string(),
+                (stats.letters as f32 / messages).to_string(),
+            ]
+        })
+        .collect();
+
+    Table {
+        name: "participants",
+        header: &[
+            "participant",
+            "messages",
+            "share",
+            "words",
+            "letters",
+            "words_per_message",
+            "letters_per_message",
+        ],
+        rows,
+    }
+}
+
+fn emojis(summary: &Summary) -> Table {
+    let emojis = summary.emojis();
+    let rows = emoji::ranked(&emojis)
+        .into_iter()
+        .map(|(emoji, count)| vec![emoji.clone(), count.to_string()])
+        .collect();
+
+    Table {
+        name: "emojis",
+        header: &["emoji", "count"],
+        rows,
+    }
+}
+
+pub fn build(summary: &Summary, timeline_type: TimelineType) -> Vec<Table> {
+    vec![
+        timeline(&summary.timeline(timeline_type)),
+        frequency(summary),
+        participants(summary),
+        emojis(summary),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::conversation::Conversation;
+
+    static MOCK: &str = r#"
+[2019-09-11, 01:57:17] Foo: Hey! 💩
+[2019-09-11, 13:01:00] Bar, "Baz": heyyyyyyy, 'sup 💩💩
+[2019-09-13, 22:15:00] Foo: there
+[2019-10-01, 00:00:00] Bar, "Baz": 🙄
+"#;
+
+    fn written(name: &str, delimiter: u8) -> String {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let tables = build(&Summary::from(&c), TimelineType::Monthly);
+        let table = tables.iter().find(|t| t.name == name).unwrap();
+
+        let mut out = Vec::new();
+        table.write(&mut out, delimiter).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn build_works() {
+        let c = Conversation::from_str(MOCK).unwrap();
+        let names: Vec<&str> = build(&Summary::from(&c), TimelineType::Monthly)
+            .iter()
+            .map(|t| t.name)
+            .collect();
+
+        assert_eq!(names, NAMES);
+    }
+
+    #[test]
+    fn timeline_is_long_format() {
+        let lines: Vec<String> = written("timeline", b',')
+            .lines()
+            .map(String::from)
+            .collect();
+
+        assert_eq!(
+            lines[0],
+            "bucket,participant,days,messages,words,letters,messages_per_day,words_per_day,letters_per_day"
+        );
+        assert_eq!(lines.len(), 1 + 2 * 3);
+        assert_eq!(lines[1], "2019-09-01T00:00:00,,20,3,4,23,0.15,0.2,1.15");
+        assert_eq!(lines[2], "2019-09-01T00:00:00,Foo,20,2,2,9,0.1,0.1,0.45");
+        assert_eq!(
+            lines[3],
+            r#"2019-09-01T00:00:00,"Bar, ""Baz""",20,1,2,14,0.05,0.1,0.7"#
+        );
+        assert_eq!(lines[6], "2019-10-01T00:00:00,Foo,1,0,0,0,0,0,0");
+    }
+
+    #[test]
+    fn tables_are_written_as_tsv() {
+        let participants = written("participants", b'\t');
+
+        assert_eq!(
+            participants,
+            "participant\tmessages\tshare\twords\tletters\twords_per_message\tletters_per_message\n\
+             \"Bar, \"\"Baz\"\"\"\t2\t0.5\t2\t14\t1\t7\n\
+             Foo\t2\t0.5\t2\t9\t1\t4.5\n"
+        );
+        assert_eq!(written("emojis", b'\t'), "emoji\tcount\n💩\t3\n🙄\t1\n");
+        assert!(written("frequency", b'\t').starts_with(
+            "hour\tparticipant\tmessages\n00h\t\t1\n00h\t\"Bar, \"\"Baz\"\"\"\t1\n00h\tFoo\t0\n01h\t\t1\n"
+        ));
+    }
+}
@@ -0,0 +1,428 @@
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use serde::de::IgnoredAny;
+use serde_json::Result;
+
+use crate::attachment::Attachment;
+use crate::conversation::Conversation;
+use crate::event::ChatEvent;
+use crate::message::{normalize, Message, MessageType};
+use crate::zone;
+
+// telegram desktop writes a single chat to `result.json`, media files sit next to it
+#[derive(Debug, Deserialize)]
+struct Export {
+    messages: Vec<Entry>,
+}
+
+// `text` is either a plain string or a list of strings and entities like links and mentions
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Plain(String),
+    Rich(Vec<Part>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Part {
+    Plain(String),
+    Entity { text: String },
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Text::Plain(String::new())
+    }
+}
+
+impl Text {
+    fn flatten(&self) -> String {
+        match self {
+            Text::Plain(text) => text.to_string(),
+            Text::Rich(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    Part::Plain(text) => text.as_str(),
+                    Part::Entity { text } => text.as_str(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// only the fields that matter for the analysis, media is told apart by the fields present
+#[derive(Debug, Deserialize)]
+struct Entry {
+    #[serde(rename = "type")]
+    kind: String,
+    date: NaiveDateTime,
+    date_unixtime: Option<String>,
+    #[serde(default)]
+    text: Text,
+    from: Option<String>,
+    edited: Option<IgnoredAny>,
+
+    photo: Option<String>,
+    file: Option<String>,
+    file_name: Option<String>,
+    file_size: Option<u64>,
+    photo_file_size: Option<u64>,
+    media_type: Option<String>,
+    location_information: Option<IgnoredAny>,
+    live_location_period_seconds: Option<IgnoredAny>,
+    contact_information: Option<IgnoredAny>,
+    poll: Option<IgnoredAny>,
+    self_destruct_period_seconds: Option<IgnoredAny>,
+
+    actor: Option<String>,
+    action: Option<String>,
+    title: Option<String>,
+    #[serde(default)]
+    members: Vec<Option<String>>,
+    discard_reason: Option<String>,
+}
+
+// accounts deleted since are exported with a null name
+const DELETED_ACCOUNT: &str = "Deleted Account";
+
+impl Entry {
+    fn author(&self) -> String {
+        normalize(
+            self.from
+                .as_deref()
+                .or(self.actor.as_deref())
+                .unwrap_or(DELETED_ACCOUNT),
+        )
+    }
+
+    // files left out of the export come as `(File not included. ...)` instead of a path
+    fn attachment(&self) -> Option<Attachment> {
+        let path = self.photo.as_ref().or(self.file.as_ref());
+        let name = match &self.file_name {
+            Some(name) => name.as_str(),
+            None => path.filter(|p| !p.starts_with('('))?.rsplit('/').next()?,
+        };
+
+        Some(Attachment {
+            name: name.to_string(),
+            size: self.file_size.or(self.photo_file_size),
+        })
+    }
+
+    fn message_kind(&self, attachment: &Option<Attachment>) -> MessageType {
+        if self.self_destruct_period_seconds.is_some() {
+            return MessageType::ViewOnce;
+        }
+
+        if self.photo.is_some() {
+            return MessageType::Image;
+        }
+
+        match self.media_type.as_deref() {
+            Some("sticker") => return MessageType::Sticker,
+            Some("animation") => return MessageType::Gif,
+            Some("video_file") | Some("video_message") => return MessageType::Video,
+            Some("voice_message") | Some("audio_file") => return MessageType::Audio,
+            Some(_) => return MessageType::Unknown,
+            None => {}
+        }
+
+        if self.file.is_some() {
+            let name = attachment.as_ref().map(|a| a.name.clone());
+            return MessageType::Document(name.unwrap_or_default());
+        }
+
+        if self.location_information.is_some() {
+            return match self.live_location_period_seconds {
+                Some(_) => MessageType::LiveLocation,
+                None => MessageType::Location,
+            };
+        }
+
+        if self.contact_information.is_some() {
+            return MessageType::Contact;
+        }
+
+        if self.poll.is_some() {
+            return MessageType::Poll;
+        }
+
+        MessageType::Text
+    }
+
+    // newer exports also carry the unix time, older ones only the time on the exporting computer
+    fn datetime(
+        &self,
+        zone: &Tz,
+        previous: Option<&DateTime<FixedOffset>>,
+    ) -> DateTime<FixedOffset> {
+        let timestamp = self
+            .date_unixtime
+            .as_deref()
+            .and_then(|v| v.parse().ok())
+            .and_then(|v| DateTime::from_timestamp(v, 0));
+
+        match timestamp {
+            Some(timestamp) => timestamp.with_timezone(zone).fixed_offset(),
+            None => zone::localize(&self.date, zone, previous),
+        }
+    }
+
+    fn message(&self, datetime: DateTime<FixedOffset>) -> Message {
+        let attachment = self.attachment();
+
+        Message {
+            datetime,
+            author: self.author(),
+            text: normalize(&self.text.flatten()),
+            kind: self.message_kind(&attachment),
+            event: None,
+            attachment,
+            edited: self.edited.is_some(),
+            reply_to: None,
+        }
+    }
+
+    // telegram keeps calls among the service messages, they count as messages of the caller
+    fn call(&self, datetime: DateTime<FixedOffset>) -> Message {
+        Message {
+            datetime,
+            author: self.author(),
+            text: normalize(&self.text.flatten()),
+            kind: MessageType::Call {
+                video: false,
+                missed: self.discard_reason.as_deref() == Some("missed"),
+            },
+            event: None,
+            attachment: None,
+            edited: false,
+            reply_to: None,
+        }
+    }
+
+    fn events(&self) -> Vec<ChatEvent> {
+        let by = self.author();
+        let title = self.title.clone().unwrap_or_default();
+        let members: Vec<String> = self
+            .members
+            .iter()
+            .map(|m| m.as_deref().unwrap_or(DELETED_ACCOUNT).to_string())
+            .collect();
+
+        match self.action.as_deref().unwrap_or_default() {
+            "create_group" | "create_channel" => vec![ChatEvent::Created { by, subject: title }],
+            "invite_members" => vec![ChatEvent::MemberAdded { by, members }],
+            // leaving the group is a removal of oneself
+            "remove_members" if members == [by.as_str()] => {
+                vec![ChatEvent::MemberLeft { member: by }]
+            }
+            "remove_members" => members
+                .into_iter()
+                .map(|member| ChatEvent::MemberRemoved {
+                    by: by.clone(),
+                    member,
+                })
+                .collect(),
+            "join_group_by_link" | "join_group_by_request" => {
+                vec![ChatEvent::MemberJoined { member: by }]
+            }
+            "edit_group_title" => vec![ChatEvent::SubjectChanged { by, subject: title }],
+            "edit_group_photo" | "delete_group_photo" => vec![ChatEvent::IconChanged { by }],
+            _ => Vec::new(),
+        }
+    }
+
+    // service messages have no text of their own, they are kept under the name of their action
+    fn system(&self, datetime: DateTime<FixedOffset>, event: Option<ChatEvent>) -> Message {
+        Message {
+            datetime,
+            author: self.author(),
+            text: self.action.clone().unwrap_or_default(),
+            kind: MessageType::System,
+            event,
+            attachment: None,
+            edited: false,
+            reply_to: None,
+        }
+    }
+}
+
+// dates without a unix time are read in `zone`
+pub fn parse(raw: &str, zone: &Tz) -> Result<Conversation> {
+    let export: Export = serde_json::from_str(raw)?;
+
+    let mut messages = Vec::new();
+    let mut events = Vec::new();
+    let mut previous = None;
+
+    for entry in export.messages.iter() {
+        let datetime = entry.datetime(zone, previous.as_ref());
+        previous = Some(datetime);
+
+        if entry.kind != "service" {
+            messages.push(entry.message(datetime));
+            continue;
+        }
+
+        if entry.action.as_deref() == Some("phone_call") {
+            messages.push(entry.call(datetime));
+            continue;
+        }
+
+        match entry.events().as_slice() {
+            [] => events.push(entry.system(datetime, None)),
+            found => events.extend(
+                found
+                    .iter()
+                    .map(|e| entry.system(datetime, Some(e.clone()))),
+            ),
+        }
+    }
+
+    Ok(Conversation::from_messages(messages, events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static MOCK: &str = r#"{
+  "name": "Foo and friends",
+  "type": "private_group",
+  "id": 42,
+  "messages": [
+    {
+      "id": 1,
+      "type": "service",
+      "date": "2019-09-11T01:57:17",
+      "actor": "Foo",
+      "actor_id": "user1",
+      "action": "create_group",
+      "title": "Foo and friends",
+      "members": ["Bar", null],
+      "text": ""
+    },
+    {
+      "id": 2,
+      "type": "message",
+      "date": "2019-09-11T01:57:18",
+      "from": "Foo",
+      "from_id": "user1",
+      "text": ["look at ", {"type": "link", "text": "example.com"}, "!"]
+    },
+    {
+      "id": 3,
+      "type": "message",
+      "date": "2019-09-11T01:58:00",
+      "edited": "2019-09-11T01:59:00",
+      "from": "Bar",
+      "from_id": "user2",
+      "photo": "photos/photo_1@11-09-2019_01-58-00.jpg",
+      "photo_file_size": 42,
+      "width": 800,
+      "height": 600,
+      "text": "nice"
+    },
+    {
+      "id": 4,
+      "type": "message",
+      "date": "2019-09-11T02:00:00",
+      "from": "Bar",
+      "from_id": "user2",
+      "file": "(File not included. Change data exporting settings to download.)",
+      "media_type": "sticker",
+      "sticker_emoji": "👍",
+      "text": ""
+    },
+    {
+      "id": 5,
+      "type": "service",
+      "date": "2019-09-11T02:01:00",
+      "actor": "Bar",
+      "actor_id": "user2",
+      "action": "remove_members",
+      "members": ["Bar"],
+      "text": ""
+    },
+    {
+      "id": 6,
+      "type": "service",
+      "date": "2019-09-11T02:02:00",
+      "actor": "Foo",
+      "actor_id": "user1",
+      "action": "phone_call",
+      "discard_reason": "missed",
+      "text": ""
+    }
+  ]
+}"#;
+
+    #[test]
+    fn parse_works() {
+        let c = parse(MOCK, &Tz::UTC).unwrap();
+
+        assert_eq!(c.count(), 4);
+        assert_eq!(c.participants(), &vec!["Foo", "Bar"]);
+        assert_eq!(c.first().unwrap().text, "look at example.com!");
+        assert_eq!(
+            format!("{}", c.first().unwrap().datetime.naive_local()),
+            "2019-09-11 01:57:18"
+        );
+    }
+
+    #[test]
+    fn parse_maps_media() {
+        let c = parse(MOCK, &Tz::UTC).unwrap();
+        let photo = &c.by_author(String::from("Bar")).first().cloned().unwrap();
+
+        assert_eq!(photo.kind, MessageType::Image);
+        assert_eq!(photo.text, "nice");
+        assert!(photo.edited);
+        assert_eq!(
+            photo.attachment,
+            Some(Attachment {
+                name: String::from("photo_1@11-09-2019_01-58-00.jpg"),
+                size: Some(42),
+            })
+        );
+
+        let sticker = c.by_author(String::from("Bar")).last().cloned().unwrap();
+        assert_eq!(sticker.kind, MessageType::Sticker);
+        assert_eq!(sticker.attachment, None);
+
+        assert_eq!(
+            c.last().unwrap().kind,
+            MessageType::Call {
+                video: false,
+                missed: true
+            }
+        );
+    }
+
+    #[test]
+    fn parse_maps_service_messages() {
+        let c = parse(MOCK, &Tz::UTC).unwrap();
+        let events: Vec<Option<ChatEvent>> = c.events().iter().map(|m| m.event.clone()).collect();
+
+        assert_eq!(
+            events,
+            vec![
+                Some(ChatEvent::Created {
+                    by: String::from("Foo"),
+                    subject: String::from("Foo and friends"),
+                }),
+                Some(ChatEvent::MemberLeft {
+                    member: String::from("Bar"),
+                }),
+            ]
+        );
+        assert_eq!(c.events()[0].text, "create_group");
+    }
+
+    #[test]
+    fn parse_rejects_other_json() {
+        assert!(parse(r#"{"foo": "bar"}"#, &Tz::UTC).is_err());
+        assert!(parse("[2019-09-11, 01:57:17] Foo: Bar", &Tz::UTC).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use chrono::prelude::*;
+use chrono::{Duration, LocalResult};
+use chrono_tz::Tz;
+
+use crate::message::Message;
+
+// iana names like `Europe/Berlin`
+pub fn find(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+pub fn utc(naive: &NaiveDateTime) -> DateTime<FixedOffset> {
+    Utc.from_utc_datetime(naive).fixed_offset()
+}
+
+// places the wall clock time of an export in the zone of the phone that made it. when clocks
+// fall back an hour happens twice, the reading that doesn't go back in time from the message
+// before is picked. times skipped when clocks spring forward are read with the offset from
+// before the change
+pub fn localize(
+    naive: &NaiveDateTime,
+    zone: &Tz,
+    previous: Option<&DateTime<FixedOffset>>,
+) -> DateTime<FixedOffset> {
+    match zone.from_local_datetime(naive) {
+        LocalResult::Single(datetime) => datetime.fixed_offset(),
+        LocalResult::Ambiguous(earliest, latest) => {
+            let earliest = earliest.fixed_offset();
+
+            if previous.is_some_and(|previous| earliest < *previous) {
+                latest.fixed_offset()
+            } else {
+                earliest
+            }
+        }
+        LocalResult::None => {
+            let offset = zone
+                .offset_from_utc_datetime(&(*naive - Duration::days(1)))
+                .fix();
+            let datetime = *naive - Duration::seconds(i64::from(offset.local_minus_utc()));
+
+            zone.from_utc_datetime(&datetime).fixed_offset()
+        }
+    }
+}
+
+// moves the timestamps of a message to the zone the report is made for
+pub fn convert(message: &mut Message, zone: &Tz) {
+    message.datetime = message.datetime.with_timezone(zone).fixed_offset();
+    message.reply_to = message
+        .reply_to
+        .map(|datetime| datetime.with_timezone(zone).fixed_offset());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(raw: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn localize_works() {
+        let berlin = find("Europe/Berlin").unwrap();
+
+        assert_eq!(
+            localize(&naive("2019-09-11 01:57"), &berlin, None).to_rfc3339(),
+            "2019-09-11T01:57:00+02:00"
+        );
+        assert_eq!(
+            localize(&naive("2019-12-11 01:57"), &berlin, None).to_rfc3339(),
+            "2019-12-11T01:57:00+01:00"
+        );
+    }
+
+    #[test]
+    fn localize_handles_clocks_falling_back() {
+        let berlin = find("Europe/Berlin").unwrap();
+
+        let first = localize(&naive("2019-10-27 02:30"), &berlin, None);
+        let second = localize(&naive("2019-10-27 02:10"), &berlin, Some(&first));
+        let third = localize(&naive("2019-10-27 02:20"), &berlin, Some(&second));
+
+        assert_eq!(first.to_rfc3339(), "2019-10-27T02:30:00+02:00");
+        assert_eq!(second.to_rfc3339(), "2019-10-27T02:10:00+01:00");
+        assert_eq!(third.to_rfc3339(), "2019-10-27T02:20:00+01:00");
+    }
+
+    #[test]
+    fn localize_handles_clocks_springing_forward() {
+        let berlin = find("Europe/Berlin").unwrap();
+
+        assert_eq!(
+            localize(&naive("2019-03-31 02:30"), &berlin, None).to_rfc3339(),
+            "2019-03-31T03:30:00+02:00"
+        );
+    }
+
+    #[test]
+    fn convert_works() {
+        let mut m = Message::from_str("2019-09-11, 23:30:00", "Foo", "hey").unwrap();
+        convert(&mut m, &find("Asia/Karachi").unwrap());
+
+        assert_eq!(m.datetime.to_rfc3339(), "2019-09-12T04:30:00+05:00");
+        assert!(find("Mars/Olympus").is_none());
+    }
+}